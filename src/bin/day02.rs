@@ -1,30 +1,86 @@
-use std::cmp::max;
-use std::collections::HashMap;
-use std::fs;
+use std::{collections::BTreeMap, env, fs};
 
 use lazy_static::lazy_static;
 use regex::Regex;
 
 lazy_static! {
-    static ref ID_RE: Regex = Regex::new(r"Game (\d+): ").expect("Should be a valid regex");
-    static ref DRAWS_RE: Regex =
-        Regex::new(r"(?<n>\d+) (?<color>red|green|blue)").expect("Should be a valid regex");
+    static ref CUBES_RE: Regex =
+        Regex::new(r"(?<n>\d+) (?<color>\w+)").expect("Should be a valid regex");
 }
 
-fn part1(input: &str) -> i32 {
-    let max_possible = HashMap::from([("red", 12), ("green", 13), ("blue", 14)]);
-    input
-        .lines()
-        .filter(|line| {
-            get_draws(line).iter().all(|draw| {
-                draw.iter().all(|(n, color)| {
-                    n <= max_possible
-                        .get(&color.as_str())
-                        .expect("Should be a valid color")
-                })
-            })
+/// Number of cubes of each color shown at once.
+type Draw = BTreeMap<String, i64>;
+
+/// Number of cubes of each color in the bag.
+type Bag = BTreeMap<String, i64>;
+
+#[derive(Debug, PartialEq, Eq)]
+struct Game {
+    id: i64,
+    draws: Vec<Draw>,
+}
+
+impl Game {
+    fn from(line: &str) -> Game {
+        let (id, draws) = line.split_once(": ").expect("Should be a valid game");
+        let id = id
+            .strip_prefix("Game ")
+            .expect("Should find a game id")
+            .parse()
+            .expect("Should be a valid number");
+        let draws = draws.split("; ").map(parse_cubes).collect();
+        Game { id, draws }
+    }
+
+    /// Whether no draw shows more cubes of a color than the bag has.
+    fn is_possible(&self, bag: &Bag) -> bool {
+        self.draws.iter().all(|draw| {
+            draw.iter()
+                .all(|(color, n)| n <= bag.get(color).unwrap_or(&0))
+        })
+    }
+
+    /// The fewest cubes of each color shown that make the game possible.
+    fn minimum_bag(&self) -> Bag {
+        let mut bag = Bag::new();
+        for (color, &n) in self.draws.iter().flatten() {
+            let count = bag.entry(color.clone()).or_insert(0);
+            *count = n.max(*count);
+        }
+        bag
+    }
+}
+
+/// Cubes like `3 blue, 4 red`, for both draws and bags.
+fn parse_cubes(s: &str) -> BTreeMap<String, i64> {
+    CUBES_RE
+        .captures_iter(s)
+        .map(|group| {
+            let n = group["n"].parse().expect("Should be a valid number");
+            (group["color"].to_string(), n)
         })
-        .map(get_id)
+        .collect()
+}
+
+/// Product of the number of cubes of each of `colors` in the bag, a missing color having none.
+fn power(bag: &Bag, colors: &[&str]) -> i64 {
+    colors
+        .iter()
+        .map(|&color| bag.get(color).copied().unwrap_or(0))
+        .product()
+}
+
+fn possible_games<'a>(games: &'a [Game], bag: &Bag) -> Vec<&'a Game> {
+    games.iter().filter(|game| game.is_possible(bag)).collect()
+}
+
+fn part1(input: &str) -> i64 {
+    let games = input.lines().map(Game::from).collect::<Vec<_>>();
+    let bag = parse_cubes("12 red, 13 green, 14 blue");
+
+    possible_games(&games, &bag)
+        .iter()
+        .map(|game| game.id)
         .sum()
 }
 
@@ -32,61 +88,35 @@ fn part2(input: &str) -> i64 {
     // Note that the result may be fairly large.
     input
         .lines()
-        .map(|line| {
-            let mut min_possible = HashMap::from([
-                ("red".to_string(), 0i64),
-                ("green".to_string(), 0i64),
-                ("blue".to_string(), 0i64),
-            ]);
-            for draw in get_draws(line) {
-                for (n, color) in draw {
-                    min_possible
-                        .entry(color)
-                        .and_modify(|e| *e = max(*e, n.into()));
-                }
-            }
-            min_possible.values().product::<i64>()
-        })
+        .map(|line| power(&Game::from(line).minimum_bag(), &["red", "green", "blue"]))
         .sum()
 }
 
-fn get_id(line: &str) -> i32 {
-    let caps = ID_RE.captures(line).expect("Should find a game id");
-    caps.get(1)
-        .expect("Should find an id")
-        .as_str()
-        .parse::<i32>()
-        .expect("Should be a valid number")
-}
-
-fn get_draws(line: &str) -> Vec<Vec<(i32, String)>> {
-    let (_, game) = line.split_once(": ").expect("Should be a valid game");
-    game.split("; ")
-        .map(|draw| {
-            DRAWS_RE
-                .captures_iter(draw)
-                .map(|group| {
-                    let n = group["n"].parse::<i32>().expect("Should be a valid number");
-                    let color = group["color"].to_string().clone();
-                    (n, color)
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>()
-}
-
 fn main() {
     let file_path = "data/day02_input.txt";
 
     let input = fs::read_to_string(file_path).expect("Should have been able to read the file");
 
+    // List the games possible with another bag with `day02 "12 red, 13 green, 14 blue"`.
+    if let Some(spec) = env::args().nth(1) {
+        let bag = parse_cubes(&spec);
+        let games = input.lines().map(Game::from).collect::<Vec<_>>();
+        let ids = possible_games(&games, &bag)
+            .iter()
+            .map(|game| game.id)
+            .collect::<Vec<_>>();
+        println!("{:?}", ids);
+        println!("{}", ids.iter().sum::<i64>());
+        return;
+    }
+
     println!("{}", part1(&input));
     println!("{}", part2(&input));
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use super::{parse_cubes, part1, part2, possible_games, power, Game};
 
     #[test]
     fn test_part1() {
@@ -108,5 +138,37 @@ Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
 Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
 
         assert_eq!(part2(input), 2286);
+        assert_eq!(part2("Game 1: 3 red, 2 green"), 0);
+        assert_eq!(part2("Game 1: 3 red, 2 green, 1 blue, 5 purple"), 6);
+    }
+
+    #[test]
+    fn test_game() {
+        let game =
+            Game::from("Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red");
+
+        assert_eq!(game.id, 3);
+        assert_eq!(game.draws.len(), 3);
+        assert_eq!(game.draws[2], parse_cubes("1 red, 5 green"));
+        assert_eq!(game.minimum_bag(), parse_cubes("20 red, 13 green, 6 blue"));
+        assert_eq!(power(&game.minimum_bag(), &["red", "green", "blue"]), 1560);
+        assert!(!game.is_possible(&parse_cubes("12 red, 13 green, 14 blue")));
+        assert!(game.is_possible(&game.minimum_bag()));
+
+        // Any color, a missing one having no cubes.
+        let games = [
+            Game::from("Game 1: 2 purple, 1 red; 3 purple"),
+            Game::from("Game 2: 1 red"),
+            Game::from("Game 7: 1 teal"),
+        ];
+        let ids = |bag: &str| {
+            possible_games(&games, &parse_cubes(bag))
+                .iter()
+                .map(|game| game.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("3 purple, 1 red"), vec![1, 2]);
+        assert_eq!(ids("2 purple, 5 red, 1 teal"), vec![2, 7]);
+        assert_eq!(ids(""), Vec::<i64>::new());
     }
 }
@@ -1,99 +1,136 @@
-use std::fs;
+use std::{collections::HashMap, env, fs, ops::Range};
 
-use aoc_2023::{grid_neighbors8, parse_grid};
+use aoc_2023::parse_grid;
 use itertools::Itertools;
 
 fn part1(input: &str) -> i64 {
-    let grid = parse_grid(input);
+    let schematic = Schematic::from(input);
 
-    grid.iter()
-        .enumerate()
-        .map(|(i, line)| line_sum(i, line, &grid))
-        .sum()
+    schematic.part_numbers().map(|n| n.value).sum()
 }
 
 fn part2(input: &str) -> i64 {
-    let grid = parse_grid(input);
-
-    let (numbers, numbers_grid) = find_numbers(&grid);
-    grid.iter()
-        .enumerate()
-        .flat_map(|(i, line)| line.iter().enumerate().map(move |(j, c)| (i, j, *c)))
-        .filter(|(_, _, c)| *c == '*')
-        .map(|(i, j, _)| {
-            grid_neighbors8(&numbers_grid, (i, j))
+    let schematic = Schematic::from(input);
+
+    schematic
+        .symbols_with_numbers('*', 2)
+        .map(|s| {
+            schematic.numbers_of[s]
                 .iter()
-                .filter_map(|(_, n)| *n)
-                .unique()
-                .collect_vec()
+                .map(|&n| schematic.numbers[n].value)
+                .product::<i64>()
         })
-        .filter(|neis| neis.len() == 2)
-        .map(|neis| numbers[neis[0]] * numbers[neis[1]])
         .sum()
 }
 
-fn line_sum(i: usize, line: &[char], grid: &Vec<Vec<char>>) -> i64 {
-    let mut res = 0;
-    let mut cur_part = 0;
-    let mut cur_valid = false;
-    for (j, c) in line.iter().enumerate() {
-        if c.is_ascii_digit() {
-            cur_part *= 10;
-            cur_part += c.to_digit(10).expect("Should be a valid digit") as i64;
-            if !cur_valid
-                && grid_neighbors8(grid, (i, j))
-                    .iter()
-                    .any(|(_, c)| *c != '.' && !c.is_ascii_digit())
-            {
-                cur_valid = true;
-            }
-        } else {
-            if cur_valid {
-                res += cur_part;
+#[derive(Debug, PartialEq, Eq)]
+struct Number {
+    value: i64,
+    line: usize,
+    columns: Range<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Symbol {
+    name: char,
+    position: (usize, usize),
+}
+
+/// Numbers and symbols, with which are next to which (diagonals included).
+struct Schematic {
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+    /// Indices of the symbols next to each number.
+    symbols_of: Vec<Vec<usize>>,
+    /// Indices of the numbers next to each symbol.
+    numbers_of: Vec<Vec<usize>>,
+}
+
+impl Schematic {
+    fn from(input: &str) -> Schematic {
+        let grid = parse_grid(input);
+
+        let mut numbers = vec![];
+        let mut symbols = vec![];
+        for (i, line) in grid.iter().enumerate() {
+            let mut j = 0;
+            while j < line.len() {
+                if line[j].is_ascii_digit() {
+                    let start = j;
+                    while j < line.len() && line[j].is_ascii_digit() {
+                        j += 1;
+                    }
+                    let value = line[start..j]
+                        .iter()
+                        .collect::<String>()
+                        .parse()
+                        .expect("Should be a valid number");
+                    numbers.push(Number {
+                        value,
+                        line: i,
+                        columns: start..j,
+                    });
+                    continue;
+                }
+                if line[j] != '.' {
+                    symbols.push(Symbol {
+                        name: line[j],
+                        position: (i, j),
+                    });
+                }
+                j += 1;
             }
-            cur_part = 0;
-            cur_valid = false;
         }
-    }
-    // Let's not forget the last part.
-    if cur_valid {
-        res += cur_part;
-    }
-    res
-}
 
-fn find_numbers(grid: &Vec<Vec<char>>) -> (Vec<i64>, Vec<Vec<Option<usize>>>) {
-    let mut numbers = vec![];
-    let mut numbers_grid = vec![];
-    let mut in_number = false;
-    for line in grid {
-        numbers_grid.push(vec![]);
-        for c in line {
-            if c.is_ascii_digit() {
-                let d = c.to_digit(10).expect("Should be a valid digit") as i64;
-                if in_number {
-                    let number = numbers.last_mut().expect("Should have a number");
-                    *number *= 10;
-                    *number += d;
-                } else {
-                    in_number = true;
-                    numbers.push(d);
+        let symbol_at: HashMap<_, _> = symbols
+            .iter()
+            .enumerate()
+            .map(|(s, symbol)| (symbol.position, s))
+            .collect();
+        let mut symbols_of = vec![vec![]; numbers.len()];
+        let mut numbers_of = vec![vec![]; symbols.len()];
+        for (n, number) in numbers.iter().enumerate() {
+            let lines = number.line.saturating_sub(1)..=number.line + 1;
+            let columns = number.columns.start.saturating_sub(1)..=number.columns.end;
+            for position in lines.cartesian_product(columns) {
+                if let Some(&s) = symbol_at.get(&position) {
+                    symbols_of[n].push(s);
+                    numbers_of[s].push(n);
                 }
-                numbers_grid
-                    .last_mut()
-                    .expect("Should have a numbers line")
-                    .push(Some(numbers.len() - 1));
-            } else {
-                in_number = false;
-                numbers_grid
-                    .last_mut()
-                    .expect("Should have a numbers line")
-                    .push(None);
             }
         }
-        in_number = false;
+
+        Schematic {
+            numbers,
+            symbols,
+            symbols_of,
+            numbers_of,
+        }
+    }
+
+    /// Numbers next to any symbol.
+    fn part_numbers(&self) -> impl Iterator<Item = &Number> {
+        self.numbers
+            .iter()
+            .zip(&self.symbols_of)
+            .filter(|(_, symbols)| !symbols.is_empty())
+            .map(|(number, _)| number)
+    }
+
+    /// Numbers next to a given symbol, each once.
+    fn numbers_next_to(&self, name: char) -> impl Iterator<Item = &Number> {
+        self.numbers
+            .iter()
+            .zip(&self.symbols_of)
+            .filter(move |(_, symbols)| symbols.iter().any(|&s| self.symbols[s].name == name))
+            .map(|(number, _)| number)
+    }
+
+    /// Indices of the symbols `name` next to exactly `k` numbers.
+    fn symbols_with_numbers(&self, name: char, k: usize) -> impl Iterator<Item = usize> + '_ {
+        (0..self.symbols.len())
+            .filter(move |&s| self.symbols[s].name == name && self.numbers_of[s].len() == k)
     }
-    (numbers, numbers_grid)
 }
 
 fn main() {
@@ -101,13 +138,27 @@ fn main() {
 
     let input = fs::read_to_string(file_path).expect("Should have been able to read the file");
 
+    // Sum the numbers next to a symbol with `day03 <symbol>`.
+    if let Some(name) = env::args().nth(1) {
+        let name = name.chars().next().expect("Usage: day03 [symbol]");
+        let schematic = Schematic::from(&input);
+        println!(
+            "{}",
+            schematic
+                .numbers_next_to(name)
+                .map(|n| n.value)
+                .sum::<i64>()
+        );
+        return;
+    }
+
     println!("{}", part1(&input));
     println!("{}", part2(&input));
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use super::{part1, part2, Number, Schematic, Symbol};
 
     #[test]
     fn test_part1() {
@@ -140,4 +191,53 @@ mod tests {
 
         assert_eq!(part2(input), 467835);
     }
+
+    #[test]
+    fn test_schematic() {
+        let input = "467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..";
+        let schematic = Schematic::from(input);
+
+        assert_eq!(schematic.numbers.len(), 10);
+        assert_eq!(
+            schematic.numbers[2],
+            Number {
+                value: 35,
+                line: 2,
+                columns: 2..4
+            }
+        );
+        assert_eq!(
+            schematic.symbols[1],
+            Symbol {
+                name: '#',
+                position: (3, 6)
+            }
+        );
+        assert_eq!(schematic.numbers_of[0], vec![0, 2]);
+        assert_eq!(schematic.symbols_of[1], Vec::<usize>::new());
+        assert_eq!(
+            schematic.numbers_next_to('#').map(|n| n.value).sum::<i64>(),
+            633
+        );
+        assert_eq!(
+            schematic.symbols_with_numbers('*', 1).collect::<Vec<_>>(),
+            vec![2]
+        );
+
+        // A number next to two symbols, and one at the end of a line.
+        let schematic = Schematic::from("#12#\n..*7");
+        assert_eq!(schematic.symbols_of[0], vec![0, 1, 2]);
+        assert_eq!(schematic.symbols_of[1], vec![1, 2]);
+        assert_eq!(schematic.numbers_next_to('#').count(), 2);
+        assert_eq!(schematic.part_numbers().count(), 2);
+    }
 }
@@ -1,7 +1,4 @@
-use std::{
-    collections::{HashMap, HashSet},
-    fs,
-};
+use std::{collections::HashSet, env, fs, ops::Range};
 
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -11,15 +8,97 @@ lazy_static! {
 }
 
 fn part1(input: &str) -> i64 {
-    input
-        .lines()
-        .map(parse_game)
-        .map(|(winning, mine)| find_matches(&winning, &mine).len())
-        .map(count_points)
-        .sum()
+    let scratchcards = Scratchcards::from(input);
+
+    scratchcards.matches.iter().map(|&m| count_points(m)).sum()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Card {
+    winning: Vec<i64>,
+    mine: Vec<i64>,
+}
+
+/// A pile of cards, indexed from 0, and the instances they end up with.
+#[derive(Clone, Debug)]
+struct Scratchcards {
+    cards: Vec<Card>,
+    matches: Vec<usize>,
+    /// Instances of each card, the original included.
+    copies: Vec<i64>,
+    /// Cards that win copies of each card.
+    winners: Vec<Vec<usize>>,
+}
+
+impl Scratchcards {
+    fn from(input: &str) -> Scratchcards {
+        let cards = input.lines().map(parse_game).collect();
+        let mut scratchcards = Scratchcards {
+            cards,
+            matches: vec![],
+            copies: vec![],
+            winners: vec![],
+        };
+        scratchcards.update();
+        scratchcards
+    }
+
+    /// Each card adds its instances to a range of the next ones: count with a difference array.
+    fn update(&mut self) {
+        let n = self.cards.len();
+        self.matches = self
+            .cards
+            .iter()
+            .map(|card| find_matches(&card.winning, &card.mine).len())
+            .collect();
+        self.copies = vec![1; n];
+        self.winners = vec![vec![]; n];
+        let mut difference = vec![0; n + 1];
+        let mut won = 0;
+        for k in 0..n {
+            won += difference[k];
+            self.copies[k] += won;
+            let range = self.won_by(k);
+            difference[range.start] += self.copies[k];
+            difference[range.end] -= self.copies[k];
+            for j in range {
+                self.winners[j].push(k);
+            }
+        }
+    }
+
+    fn total(&self) -> i64 {
+        self.copies.iter().sum()
+    }
+
+    /// Cards card `k` wins a copy of, for each of its instances.
+    fn won_by(&self, k: usize) -> Range<usize> {
+        k + 1..(k + 1 + self.matches[k]).min(self.cards.len())
+    }
+
+    /// Cards that gave copies of card `k`, with how many.
+    fn sources(&self, k: usize) -> Vec<(usize, i64)> {
+        self.winners[k]
+            .iter()
+            .map(|&j| (j, self.copies[j]))
+            .collect()
+    }
+
+    /// Take card `k` out of the pile, the next cards moving up.
+    fn remove(&mut self, k: usize) -> Card {
+        let card = self.cards.remove(k);
+        self.update();
+        card
+    }
+
+    /// Change the numbers of card `k`.
+    fn replace(&mut self, k: usize, card: Card) {
+        self.cards[k] = card;
+        self.update();
+    }
 }
 
-fn parse_game(line: &str) -> (Vec<i64>, Vec<i64>) {
+fn parse_game(line: &str) -> Card {
     let (_, game) = line.split_once(": ").expect("Should be a valid game");
     let (winning, mine) = game.split_once(" | ").expect("Should be a valid game");
     let winning = RE
@@ -30,7 +109,7 @@ fn parse_game(line: &str) -> (Vec<i64>, Vec<i64>) {
         .find_iter(mine)
         .map(|m| m.as_str().parse().expect("Should be a valid integer"))
         .collect();
-    (winning, mine)
+    Card { winning, mine }
 }
 
 fn find_matches(winning: &[i64], mine: &[i64]) -> HashSet<i64> {
@@ -48,20 +127,16 @@ fn count_points(matching: usize) -> i64 {
 }
 
 fn part2(input: &str) -> i64 {
-    let mut count_by_id = HashMap::<usize, i64>::new();
-    for (i, line) in input.lines().enumerate() {
-        let id = i + 1;
-        let (winning, mine) = parse_game(line);
-        let nb_matches = find_matches(&winning, &mine).len();
-        let my_count = *count_by_id.entry(id).or_insert(1);
-        for next_id in id + 1..id + 1 + nb_matches {
-            count_by_id
-                .entry(next_id)
-                .and_modify(|counter| *counter += my_count)
-                .or_insert(1 + my_count);
-        }
-    }
-    count_by_id.values().sum()
+    Scratchcards::from(input).total()
+}
+
+/// Index of the card numbered `k` from 1, like in the input.
+fn card_index(k: &str, scratchcards: &Scratchcards, usage: &str) -> usize {
+    k.parse::<usize>()
+        .ok()
+        .filter(|k| (1..=scratchcards.cards.len()).contains(k))
+        .expect(usage)
+        - 1
 }
 
 fn main() {
@@ -69,13 +144,59 @@ fn main() {
 
     let input = fs::read_to_string(file_path).expect("Should have been able to read the file");
 
+    // Show each card's breakdown with `day04 cards`, the total without card `k` with
+    // `day04 without <k>`, or with other numbers on it with `day04 replace <k> "<winning> | <mine>"`.
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(|a| a.as_str()).collect::<Vec<_>>()[..] {
+        ["cards"] => {
+            let scratchcards = Scratchcards::from(&input);
+            for k in 0..scratchcards.cards.len() {
+                println!(
+                    "Card {}: {} matches, {} instances, copies from {:?}",
+                    k + 1,
+                    scratchcards.matches[k],
+                    scratchcards.copies[k],
+                    scratchcards
+                        .sources(k)
+                        .iter()
+                        .map(|(j, copies)| (j + 1, copies))
+                        .collect::<Vec<_>>()
+                );
+            }
+            return;
+        }
+        ["without", k] => {
+            let mut scratchcards = Scratchcards::from(&input);
+            let k = card_index(
+                k,
+                &scratchcards,
+                "Usage: day04 without <k>, from 1 to the last card",
+            );
+            scratchcards.remove(k);
+            println!("{}", scratchcards.total());
+            return;
+        }
+        ["replace", k, numbers] => {
+            let mut scratchcards = Scratchcards::from(&input);
+            let k = card_index(
+                k,
+                &scratchcards,
+                "Usage: day04 replace <k> \"<winning> | <mine>\", from 1 to the last card",
+            );
+            scratchcards.replace(k, parse_game(&format!("Card {}: {}", k + 1, numbers)));
+            println!("{}", scratchcards.total());
+            return;
+        }
+        _ => {}
+    }
+
     println!("{}", part1(&input));
     println!("{}", part2(&input));
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use super::{parse_game, part1, part2, Scratchcards};
 
     #[test]
     fn test_part1() {
@@ -100,4 +221,43 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
 
         assert_eq!(part2(input), 30);
     }
+
+    #[test]
+    fn test_scratchcards() {
+        let input = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
+        let scratchcards = Scratchcards::from(input);
+
+        assert_eq!(scratchcards.matches, vec![4, 2, 2, 1, 0, 0]);
+        assert_eq!(scratchcards.copies, vec![1, 2, 4, 8, 14, 1]);
+        assert_eq!(scratchcards.won_by(0), 1..5);
+        assert_eq!(scratchcards.sources(3), vec![(0, 1), (1, 2), (2, 4)]);
+        assert_eq!(scratchcards.sources(0), vec![]);
+
+        let mut without_first = scratchcards.clone();
+        let first = without_first.remove(0);
+        assert_eq!(first.winning, vec![41, 48, 83, 86, 17]);
+        assert_eq!(without_first.copies, vec![1, 2, 4, 7, 1]);
+        assert_eq!(without_first.total(), 15);
+
+        // Wins past the last card are lost.
+        let mut changed = scratchcards.clone();
+        changed.replace(4, parse_game("Card 5: 1 2 3 | 1 2 3"));
+        assert_eq!(changed.won_by(4), 5..6);
+        assert_eq!(changed.copies, vec![1, 2, 4, 8, 14, 15]);
+        assert_eq!(changed.sources(5), vec![(4, 14)]);
+        assert_eq!(scratchcards.total(), 30);
+
+        let many = (1..=5000)
+            .map(|i| format!("Card {}: 1 2 | 1 3", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let many = Scratchcards::from(&many);
+        assert_eq!(many.copies[4999], 5000);
+        assert_eq!(many.total(), 5000 * 5001 / 2);
+    }
 }
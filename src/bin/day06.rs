@@ -1,47 +1,67 @@
-use std::{fs, iter::zip};
+use std::{fs, iter::zip, ops::RangeInclusive};
 
 use aoc_2023::split_parse;
 use itertools::Itertools;
+use num::{BigInt, One, ToPrimitive, Zero};
 
 fn part1(input: &str) -> i64 {
     let mut lines = input.lines();
-    let times = split_parse(lines.next().expect("There should be a first line"));
-    let distances = split_parse(lines.next().expect("There should be a second line"));
+    let times = split_parse::<BigInt>(lines.next().expect("There should be a first line"));
+    let distances = split_parse::<BigInt>(lines.next().expect("There should be a second line"));
 
     zip(times, distances)
-        .map(|(t, d)| {
-            (0..=t)
-                .filter(|push_t| simulate_push(*push_t, t) > d)
-                .count() as i64
-        })
-        .product()
+        .map(|(t, d)| ways(&t, &d))
+        .product::<BigInt>()
+        .to_i64()
+        .expect("Should fit in an i64")
 }
 
-fn simulate_push(push_time: i64, total_time: i64) -> i64 {
+fn simulate_push(push_time: &BigInt, total_time: &BigInt) -> BigInt {
     push_time * (total_time - push_time)
 }
 
+/// Push times beating the distance: `p * (t - p) > d` between the roots of `p^2 - t p + d`.
+fn winning_pushes(time: &BigInt, distance: &BigInt) -> Option<RangeInclusive<BigInt>> {
+    let best = time / 2;
+    if simulate_push(&best, time) <= *distance {
+        return None;
+    }
+    // Here the discriminant is positive, and rounding down its root puts `start` at most one
+    // below the first winning push.
+    let root = (time * time - distance * BigInt::from(4)).sqrt();
+    let mut start = ((time - root) / BigInt::from(2)).max(BigInt::zero());
+    while simulate_push(&start, time) <= *distance {
+        start += 1;
+    }
+    let end = time - &start;
+    Some(start..=end)
+}
+
+fn ways(time: &BigInt, distance: &BigInt) -> BigInt {
+    winning_pushes(time, distance)
+        .map(|range| range.end() - range.start() + BigInt::one())
+        .unwrap_or_default()
+}
+
 fn part2(input: &str) -> i64 {
     let mut lines = input.lines();
-    let times = split_parse::<i64>(lines.next().expect("There should be a first line"));
-    let distances = split_parse::<i64>(lines.next().expect("There should be a second line"));
+    let times = split_parse::<BigInt>(lines.next().expect("There should be a first line"));
+    let distances = split_parse::<BigInt>(lines.next().expect("There should be a second line"));
 
     let time = times
         .iter()
-        .map(|i| i.to_string())
         .join("")
-        .parse::<i64>()
+        .parse::<BigInt>()
         .expect("Should be a valid integer");
     let distance = distances
         .iter()
-        .map(|i| i.to_string())
         .join("")
-        .parse::<i64>()
+        .parse::<BigInt>()
         .expect("Should be a valid integer");
 
-    (0..=time)
-        .filter(|push_t| simulate_push(*push_t, time) > distance)
-        .count() as i64
+    ways(&time, &distance)
+        .to_i64()
+        .expect("Should fit in an i64")
 }
 
 fn main() {
@@ -55,7 +75,9 @@ fn main() {
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use num::BigInt;
+
+    use super::{part1, part2, simulate_push, ways, winning_pushes};
 
     #[test]
     fn test_part1() {
@@ -72,4 +94,36 @@ Distance:  9  40  200";
 
         assert_eq!(part2(input), 71503);
     }
+
+    #[test]
+    fn test_winning_pushes() {
+        let n = |x: i64| BigInt::from(x);
+
+        assert_eq!(winning_pushes(&n(7), &n(9)), Some(n(2)..=n(5)));
+        assert_eq!(winning_pushes(&n(15), &n(40)), Some(n(4)..=n(11)));
+        assert_eq!(winning_pushes(&n(30), &n(200)), Some(n(11)..=n(19)));
+        assert_eq!(winning_pushes(&n(4), &n(4)), None);
+        assert_eq!(winning_pushes(&n(4), &n(3)), Some(n(2)..=n(2)));
+        assert_eq!(winning_pushes(&n(0), &n(0)), None);
+        assert_eq!(ways(&n(4), &n(4)), n(0));
+
+        let time = BigInt::from(10).pow(30);
+        let distance = BigInt::from(10).pow(59);
+        let range = winning_pushes(&time, &distance).expect("Should be winnable");
+        let first = range.start().clone();
+        assert_eq!(
+            first,
+            "112701665379258311482073460022"
+                .parse::<BigInt>()
+                .expect("Should be a number")
+        );
+        assert!(simulate_push(&first, &time) > distance);
+        assert!(simulate_push(&(first - 1), &time) <= distance);
+        assert_eq!(
+            ways(&time, &distance),
+            "774596669241483377035853079957"
+                .parse::<BigInt>()
+                .expect("Should be a number")
+        );
+    }
 }
@@ -1,44 +1,175 @@
-use std::{fmt, fs, str::FromStr};
+use std::{cmp::Ordering, env, fmt, fs, str::FromStr};
 
 use itertools::Itertools;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 struct Card {
     name: char,
-    value1: i64,
-    value2: i64,
+    /// `c`, `d`, `h` or `s`, only for poker.
+    suit: Option<char>,
 }
 
 #[derive(PartialEq, Eq)]
 struct Hand {
-    cards: [Card; 5],
+    cards: Vec<Card>,
     bid: i64,
-    value1: (i64, [i64; 5]),
-    value2: (i64, [i64; 5]),
 }
 
-fn part1(input: &str) -> i64 {
-    let mut hands = input
-        .lines()
-        .map(|line| line.parse::<Hand>().expect("Should be a valid hand"))
-        .collect_vec();
+/// The rules of a Camel Cards variant.
+#[derive(Debug)]
+struct Ruleset {
+    /// Cards from the weakest to the strongest, for tie-breaks.
+    order: &'static str,
+    /// Cards that can stand for any other to make the best category.
+    wild: &'static str,
+    /// From the weakest to the strongest, the sizes of the groups of same cards a hand needs,
+    /// largest first: `[3, 2]` is a full house.
+    categories: &'static [&'static [usize]],
+}
 
-    hands.sort_by_key(|h| h.value1);
+const CATEGORIES: &[&[usize]] = &[&[], &[2], &[2, 2], &[3], &[3, 2], &[4], &[5]];
 
-    hands
-        .iter()
-        .enumerate()
-        .map(|(i, hand)| ((i + 1) as i64) * hand.bid)
-        .sum::<i64>()
+const CAMEL_CARDS: Ruleset = Ruleset {
+    order: "23456789TJQKA",
+    wild: "",
+    categories: CATEGORIES,
+};
+
+const JOKERS: Ruleset = Ruleset {
+    order: "J23456789TQKA",
+    wild: "J",
+    categories: CATEGORIES,
+};
+
+impl Ruleset {
+    fn strength(&self, card: Card) -> usize {
+        self.order.find(card.name).expect("Should be a valid card")
+    }
+
+    /// Index of the strongest category reachable by replacing the wild cards.
+    fn classify(&self, cards: &[Card]) -> usize {
+        let (wild, others): (Vec<Card>, Vec<Card>) =
+            cards.iter().partition(|c| self.wild.contains(c.name));
+        let groups = others
+            .iter()
+            .counts()
+            .into_values()
+            .sorted()
+            .rev()
+            .collect_vec();
+
+        // Matching the largest groups with the largest needs wastes the fewest wild cards.
+        (0..self.categories.len())
+            .rev()
+            .find(|&category| {
+                let missing: usize = self.categories[category]
+                    .iter()
+                    .enumerate()
+                    .map(|(k, &need)| need.saturating_sub(groups.get(k).copied().unwrap_or(0)))
+                    .sum();
+                missing <= wild.len()
+            })
+            .expect("Should have a category for any hand")
+    }
 }
 
-fn part2(input: &str) -> i64 {
+/// A way to rank hands.
+trait Evaluator {
+    type Key: Ord;
+
+    /// Sort key, greater for stronger hands.
+    fn key(&self, hand: &Hand) -> Self::Key;
+
+    fn compare(&self, a: &Hand, b: &Hand) -> Ordering {
+        self.key(a).cmp(&self.key(b))
+    }
+}
+
+impl Evaluator for Ruleset {
+    type Key = (usize, Vec<usize>);
+
+    /// Category, then the strength of each card in order.
+    fn key(&self, hand: &Hand) -> Self::Key {
+        (
+            self.classify(&hand.cards),
+            hand.cards.iter().map(|&c| self.strength(c)).collect(),
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+enum PokerCategory {
+    HighCard,
+    Pair,
+    TwoPair,
+    ThreeOfAKind,
+    Straight,
+    Flush,
+    FullHouse,
+    FourOfAKind,
+    StraightFlush,
+}
+
+/// Standard five cards poker: suits matter, aces play high or low in straights,
+/// and ties are broken by the ranks of the groups, then of the kickers.
+struct Poker;
+
+const POKER_ORDER: &str = "23456789TJQKA";
+
+impl Evaluator for Poker {
+    type Key = (PokerCategory, Vec<usize>);
+
+    fn key(&self, hand: &Hand) -> Self::Key {
+        assert_eq!(hand.cards.len(), 5, "Should be a poker hand");
+        let counts = hand
+            .cards
+            .iter()
+            .map(|c| POKER_ORDER.find(c.name).expect("Should be a valid card"))
+            .counts();
+        // Largest groups first, then highest ranks.
+        let ranks = counts
+            .iter()
+            .sorted_by_key(|&(&rank, &count)| (count, rank))
+            .rev()
+            .map(|(&rank, _)| rank)
+            .collect_vec();
+        let sizes = ranks.iter().map(|rank| counts[rank]).collect_vec();
+
+        let flush = hand.cards[0].suit.is_some() && hand.cards.iter().map(|c| c.suit).all_equal();
+        let high = match ranks[..] {
+            [12, 3, 2, 1, 0] => Some(3),
+            [top, .., low] if ranks.len() == 5 && top - low == 4 => Some(top),
+            _ => None,
+        };
+
+        match (high, flush, &sizes[..]) {
+            (Some(high), true, _) => (PokerCategory::StraightFlush, vec![high]),
+            (_, _, [4, 1]) => (PokerCategory::FourOfAKind, ranks),
+            (_, _, [3, 2]) => (PokerCategory::FullHouse, ranks),
+            (_, true, _) => (PokerCategory::Flush, ranks),
+            (Some(high), false, _) => (PokerCategory::Straight, vec![high]),
+            (_, _, [3, 1, 1]) => (PokerCategory::ThreeOfAKind, ranks),
+            (_, _, [2, 2, 1]) => (PokerCategory::TwoPair, ranks),
+            (_, _, [2, 1, 1, 1]) => (PokerCategory::Pair, ranks),
+            _ => (PokerCategory::HighCard, ranks),
+        }
+    }
+}
+
+/// Hands from the weakest to the strongest.
+fn ranked(input: &str, evaluator: &impl Evaluator) -> Vec<Hand> {
     let mut hands = input
         .lines()
         .map(|line| line.parse::<Hand>().expect("Should be a valid hand"))
         .collect_vec();
 
-    hands.sort_by_key(|h| h.value2);
+    // Each key classifies the hand, so compute it once per hand.
+    hands.sort_by_cached_key(|hand| evaluator.key(hand));
+    hands
+}
+
+fn winnings(input: &str, evaluator: &impl Evaluator) -> i64 {
+    let hands = ranked(input, evaluator);
 
     hands
         .iter()
@@ -47,91 +178,28 @@ fn part2(input: &str) -> i64 {
         .sum::<i64>()
 }
 
-impl From<char> for Card {
-    fn from(name: char) -> Self {
-        let value1 = match name {
-            'A' => 14,
-            'K' => 13,
-            'Q' => 12,
-            'J' => 11,
-            'T' => 10,
-            _ => name.to_digit(10).expect("Should be a valid card") as i64,
-        };
-        let value2 = match name {
-            'A' => 13,
-            'K' => 12,
-            'Q' => 11,
-            'T' => 10,
-            'J' => 1,
-            _ => name.to_digit(10).expect("Should be a valid card") as i64,
-        };
-        Card {
-            name,
-            value1,
-            value2,
-        }
-    }
+fn part1(input: &str) -> i64 {
+    winnings(input, &CAMEL_CARDS)
 }
 
-fn cards_value1(cards: [Card; 5]) -> i64 {
-    let mut sorted = cards;
-    sorted.sort_by_key(|c| c.value1);
-    let groups = sorted.iter().group_by(|c| *c);
-    let groups_sizes = groups
-        .into_iter()
-        .map(|(_, group)| group.collect_vec().len())
-        .sorted()
-        .collect_vec();
-
-    if groups_sizes.contains(&5) {
-        7
-    } else if groups_sizes.contains(&4) {
-        6
-    } else if groups_sizes.contains(&3) && groups_sizes.contains(&2) {
-        5
-    } else if groups_sizes.contains(&3) {
-        4
-    } else if groups_sizes.ends_with(&[2, 2]) {
-        3
-    } else if groups_sizes.contains(&2) {
-        2
-    } else {
-        1
-    }
+fn part2(input: &str) -> i64 {
+    winnings(input, &JOKERS)
 }
 
-fn cards_value2(cards: [Card; 5]) -> i64 {
-    let base_cards = cards.iter().filter(|c| c.name != 'J').collect_vec();
-    let j_count = 5 - base_cards.len();
-    let mut sorted = base_cards.clone();
-    sorted.sort_by_key(|c| c.value2);
-    let groups = sorted.iter().group_by(|c| *c);
-    let groups_sizes = groups
-        .into_iter()
-        .map(|(_, group)| group.collect_vec().len())
-        .sorted()
-        .collect_vec();
-
-    if j_count == 5 || groups_sizes.contains(&(5 - j_count)) {
-        7
-    } else if groups_sizes.contains(&(4 - j_count)) {
-        6
-    } else if groups_sizes.iter().rev().take(2).sum::<usize>() >= 5 - j_count {
-        5
-    } else if groups_sizes.contains(&(3 - j_count)) {
-        4
-    } else if groups_sizes.iter().rev().take(2).sum::<usize>() >= 4 - j_count {
-        3
-    } else if groups_sizes.contains(&(2 - j_count)) {
-        2
-    } else {
-        1
+impl From<char> for Card {
+    fn from(name: char) -> Self {
+        Card { name, suit: None }
     }
 }
 
 impl fmt::Debug for Hand {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let cards_fmt = self.cards.iter().map(|c| c.name).collect::<String>();
+        let cards_fmt = self
+            .cards
+            .iter()
+            .flat_map(|c| [Some(c.name), c.suit])
+            .flatten()
+            .collect::<String>();
 
         write!(f, "{cards_fmt} {}", self.bid)
     }
@@ -146,45 +214,53 @@ impl FromStr for Hand {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let (cards, bid) = s.split_whitespace().collect_tuple().ok_or(ParseHandError)?;
 
-        let cards = cards
-            .chars()
-            .map(Card::from)
-            .collect_vec()
-            .try_into()
-            .map_err(|_| ParseHandError)?;
+        // Each card, with an optional suit: `32T3K` or `As3d3c2h2s`, but not both.
+        let mut chars = cards.chars().peekable();
+        let mut cards = vec![];
+        while let Some(name) = chars.next() {
+            if !POKER_ORDER.contains(name) {
+                return Err(ParseHandError);
+            }
+            let suit = chars.next_if(|c| "cdhs".contains(*c));
+            cards.push(Card { name, suit });
+        }
+        if !cards.iter().map(|c| c.suit.is_some()).all_equal() {
+            return Err(ParseHandError);
+        }
+        // Suited cards come from a single deck, five at a time.
+        if cards.iter().any(|c| c.suit.is_some())
+            && (cards.len() != 5 || !cards.iter().all_unique())
+        {
+            return Err(ParseHandError);
+        }
         let bid = bid.parse().map_err(|_| ParseHandError)?;
 
-        let value1 = (
-            cards_value1(cards),
-            cards
-                .iter()
-                .map(|c| c.value1)
-                .collect_vec()
-                .try_into()
-                .map_err(|_| ParseHandError)?,
-        );
-        let value2 = (
-            cards_value2(cards),
-            cards
-                .iter()
-                .map(|c| c.value2)
-                .collect_vec()
-                .try_into()
-                .map_err(|_| ParseHandError)?,
-        );
-
-        Ok(Hand {
-            cards,
-            bid,
-            value1,
-            value2,
-        })
+        Ok(Hand { cards, bid })
     }
 }
 
 fn main() {
     let file_path = "data/day07_input.txt";
 
+    // Rank poker hands, like `AsKsQsJsTs 10`, with `day07 poker <file>`.
+    if env::args().nth(1).as_deref() == Some("poker") {
+        let file_path = env::args().nth(2).expect("Usage: day07 poker <file>");
+        let input = fs::read_to_string(file_path).expect("Should have been able to read the file");
+        let hands = ranked(&input, &Poker);
+        for (k, hand) in hands.iter().enumerate() {
+            // Tied hands still get different ranks, in input order.
+            let tie = k > 0 && Poker.compare(&hands[k - 1], hand) == Ordering::Equal;
+            println!(
+                "{:?} {:?}{}",
+                hand,
+                Poker.key(hand).0,
+                if tie { " (tie)" } else { "" }
+            );
+        }
+        println!("{}", winnings(&input, &Poker));
+        return;
+    }
+
     let input = fs::read_to_string(file_path).expect("Should have been able to read the file");
 
     println!("{}", part1(&input));
@@ -193,7 +269,14 @@ fn main() {
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use itertools::Itertools;
+
+    use std::cmp::Ordering;
+
+    use super::{
+        part1, part2, ranked, winnings, Card, Evaluator, Hand, Poker, PokerCategory, Ruleset,
+        CAMEL_CARDS, CATEGORIES, JOKERS,
+    };
 
     #[test]
     fn test_part1() {
@@ -216,4 +299,86 @@ QQQJA 483";
 
         assert_eq!(part2(input), 5905);
     }
+
+    #[test]
+    fn test_classify() {
+        let cards = |s: &str| s.chars().map(Card::from).collect_vec();
+
+        assert_eq!(CAMEL_CARDS.classify(&cards("32T3K")), 1);
+        assert_eq!(CAMEL_CARDS.classify(&cards("KTJJT")), 2);
+        assert_eq!(JOKERS.classify(&cards("KTJJT")), 5);
+        assert_eq!(JOKERS.classify(&cards("JJJJJ")), 6);
+        assert_eq!(JOKERS.classify(&cards("2345J")), 1);
+        assert_eq!(JOKERS.classify(&cards("22J33")), 4);
+
+        // Any number of cards and wild cards.
+        let wild_deuces = Ruleset {
+            order: "23456789TJQKA",
+            wild: "2J",
+            categories: CATEGORIES,
+        };
+        assert_eq!(wild_deuces.classify(&cards("2J3")), 3);
+        assert_eq!(wild_deuces.classify(&cards("A2KQJ98")), 3);
+        assert_eq!(wild_deuces.classify(&cards("AAKKQQ")), 2);
+        assert_eq!(wild_deuces.classify(&cards("")), 0);
+        assert!(JOKERS.strength(Card::from('J')) < JOKERS.strength(Card::from('2')));
+    }
+
+    #[test]
+    fn test_poker() {
+        let hand = |s: &str| {
+            format!("{} 1", s)
+                .parse::<Hand>()
+                .expect("Should be a hand")
+        };
+        let category = |s: &str| Poker.key(&hand(s)).0;
+
+        assert_eq!(category("AsKsQsJsTs"), PokerCategory::StraightFlush);
+        assert_eq!(category("5d4d3d2dAd"), PokerCategory::StraightFlush);
+        assert_eq!(category("9c9d9h9s2c"), PokerCategory::FourOfAKind);
+        assert_eq!(category("3c3d3h2s2c"), PokerCategory::FullHouse);
+        assert_eq!(category("Kh9h7h4h2h"), PokerCategory::Flush);
+        assert_eq!(category("Ac2d3h4s5c"), PokerCategory::Straight);
+        assert_eq!(category("QcKdAh2s3c"), PokerCategory::HighCard);
+        assert_eq!(category("7c7d7h2sKc"), PokerCategory::ThreeOfAKind);
+        assert_eq!(category("7c7d2h2sKc"), PokerCategory::TwoPair);
+        assert_eq!(category("7c7d3h2sKc"), PokerCategory::Pair);
+        // No suits, no flush.
+        assert_eq!(category("AKQJT"), PokerCategory::Straight);
+
+        let compare = |a: &str, b: &str| Poker.compare(&hand(a), &hand(b));
+        assert_eq!(compare("5c4d3h2sAc", "6c5d4h3s2c"), Ordering::Less);
+        assert_eq!(compare("AsAd9c5h2s", "AhAc8d7s6s"), Ordering::Greater);
+        assert_eq!(compare("3c3d3h2s2c", "2c2d2hAsAc"), Ordering::Greater);
+        assert_eq!(compare("KhQd9c5h2s", "KsQh9d5c2c"), Ordering::Equal);
+        assert_eq!(compare("Kh9h7h4h2h", "AcKdQhJsTc"), Ordering::Greater);
+
+        let input = "AsAd9c5h2s 10
+Kh9h7h4h2h 20
+5c4d3h2sAc 30";
+        let order = ranked(input, &Poker)
+            .iter()
+            .map(|h| format!("{:?}", h))
+            .collect_vec();
+        assert_eq!(
+            order,
+            vec!["AsAd9c5h2s 10", "5c4d3h2sAc 30", "Kh9h7h4h2h 20"]
+        );
+        assert_eq!(winnings(input, &Poker), 10 + 2 * 30 + 3 * 20);
+    }
+
+    #[test]
+    fn test_parse_hand() {
+        assert!("32T3K 765".parse::<Hand>().is_ok());
+        assert!("As3d3c2h2s 1".parse::<Hand>().is_ok());
+        assert!("32X3K 765".parse::<Hand>().is_err());
+        assert!("32t3K 765".parse::<Hand>().is_err());
+        assert!("As3d3c2h2 1".parse::<Hand>().is_err());
+        assert!("A3d3c2h2s 1".parse::<Hand>().is_err());
+        assert!("AsKs 1".parse::<Hand>().is_err());
+        assert!("AsKsQsJsTs9s 1".parse::<Hand>().is_err());
+        assert!("AsAs3c2h2s 1".parse::<Hand>().is_err());
+        assert!("32T3K".parse::<Hand>().is_err());
+        assert!("32T3K bid".parse::<Hand>().is_err());
+    }
 }
@@ -1,33 +1,94 @@
-use std::fs;
+use std::{env, fs};
 
 use aoc_2023::split_parse;
 use itertools::Itertools;
+use num::{BigInt, BigRational, One, ToPrimitive, Zero};
 
 fn part1(input: &str) -> i64 {
     let values = input.lines().map(split_parse::<i64>).collect_vec();
-    values.iter().map(|v| find_next(v)).sum()
-}
-
-fn find_next(v: &[i64]) -> i64 {
-    if v.iter().all(|&x| x == 0) {
-        0
-    } else {
-        let diffs = v.windows(2).map(|w| w[1] - w[0]).collect_vec();
-        v.last().expect("Should have a value") + find_next(&diffs)
-    }
+    values.iter().map(|v| predict(v, v.len() as i64)).sum()
 }
 
 fn part2(input: &str) -> i64 {
     let values = input.lines().map(split_parse::<i64>).collect_vec();
-    values.iter().map(|v| find_previous(v)).sum()
+    values.iter().map(|v| predict(v, -1)).sum()
+}
+
+fn predict(history: &[i64], index: i64) -> i64 {
+    Polynomial::fit(history)
+        .value(index)
+        .to_i64()
+        .expect("Should fit in an i64")
+}
+
+/// The polynomial through a history at indices 0, 1, 2..., in Newton form:
+/// `p(x) = sum(newton[k] * binomial(x, k))`, `newton[k]` being the first `k`-th difference.
+struct Polynomial {
+    newton: Vec<BigInt>,
+    /// Whether the differences reached zero: otherwise a longer history could raise the degree.
+    confirmed: bool,
 }
 
-fn find_previous(v: &[i64]) -> i64 {
-    if v.iter().all(|&x| x == 0) {
-        0
-    } else {
-        let diffs = v.windows(2).map(|w| w[1] - w[0]).collect_vec();
-        v.first().expect("Should have a value") - find_previous(&diffs)
+impl Polynomial {
+    fn fit(history: &[i64]) -> Polynomial {
+        let mut row = history.iter().map(|&x| BigInt::from(x)).collect_vec();
+        let mut newton = vec![];
+        let mut confirmed = false;
+        while !row.is_empty() {
+            if row.iter().all(Zero::is_zero) {
+                confirmed = true;
+                break;
+            }
+            newton.push(row[0].clone());
+            row = row.windows(2).map(|w| &w[1] - &w[0]).collect();
+        }
+        while newton.last().is_some_and(Zero::is_zero) {
+            newton.pop();
+        }
+        Polynomial { newton, confirmed }
+    }
+
+    /// `None` for the zero polynomial.
+    fn degree(&self) -> Option<usize> {
+        self.newton.len().checked_sub(1)
+    }
+
+    /// Value at any index, negative ones being before the history.
+    fn value(&self, x: i64) -> BigInt {
+        let x = BigInt::from(x);
+        let mut binomial = BigInt::one();
+        let mut res = BigInt::zero();
+        for (k, d) in self.newton.iter().enumerate() {
+            if k > 0 {
+                // binomial(x, k) = binomial(x, k - 1) * (x - k + 1) / k, exactly.
+                binomial = binomial * (&x - BigInt::from(k - 1)) / BigInt::from(k);
+            }
+            res += d * &binomial;
+        }
+        res
+    }
+
+    /// Coefficients of `1, x, x^2...`.
+    fn coefficients(&self) -> Vec<BigRational> {
+        // x (x - 1) ... (x - k + 1), lowest degree first.
+        let mut falling = vec![BigInt::one()];
+        let mut factorial = BigInt::one();
+        let mut res = vec![BigRational::zero(); self.newton.len()];
+        for (k, d) in self.newton.iter().enumerate() {
+            if k > 0 {
+                let mut next = vec![BigInt::zero(); falling.len() + 1];
+                for (i, c) in falling.iter().enumerate() {
+                    next[i + 1] += c;
+                    next[i] -= c * BigInt::from(k - 1);
+                }
+                falling = next;
+                factorial *= BigInt::from(k);
+            }
+            for (i, c) in falling.iter().enumerate() {
+                res[i] += BigRational::new(d * c, factorial.clone());
+            }
+        }
+        res
     }
 }
 
@@ -36,13 +97,36 @@ fn main() {
 
     let input = fs::read_to_string(file_path).expect("Should have been able to read the file");
 
+    // Describe each history and its value at any index with `day09 <index>`.
+    if let Some(index) = env::args().nth(1) {
+        let index = index.parse().expect("Usage: day09 [index]");
+        for history in input.lines().map(split_parse::<i64>) {
+            let polynomial = Polynomial::fit(&history);
+            let coefficients = polynomial.coefficients().iter().join(", ");
+            println!(
+                "degree {:?}{}, coefficients [{}], value {}",
+                polynomial.degree(),
+                if polynomial.confirmed {
+                    ""
+                } else {
+                    " (unconfirmed)"
+                },
+                coefficients,
+                polynomial.value(index)
+            );
+        }
+        return;
+    }
+
     println!("{}", part1(&input));
     println!("{}", part2(&input));
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use num::{BigInt, BigRational};
+
+    use super::{part1, part2, Polynomial};
 
     #[test]
     fn test_part1() {
@@ -61,4 +145,44 @@ mod tests {
 
         assert_eq!(part2(input), 2);
     }
+
+    #[test]
+    fn test_polynomial() {
+        let linear = Polynomial::fit(&[0, 3, 6, 9, 12, 15]);
+        assert_eq!(linear.degree(), Some(1));
+        assert_eq!(linear.value(6), BigInt::from(18));
+        assert_eq!(linear.value(-1), BigInt::from(-3));
+
+        let triangular = Polynomial::fit(&[1, 3, 6, 10, 15, 21]);
+        assert_eq!(triangular.degree(), Some(2));
+        assert!(triangular.confirmed);
+        assert_eq!(
+            triangular.coefficients(),
+            vec![
+                BigRational::from_integer(1.into()),
+                BigRational::new(3.into(), 2.into()),
+                BigRational::new(1.into(), 2.into()),
+            ]
+        );
+        assert_eq!(
+            triangular.value(1_000_000_000_000),
+            "500000000001500000000001"
+                .parse::<BigInt>()
+                .expect("Should be a number")
+        );
+
+        let cubic = Polynomial::fit(&[10, 13, 16, 21, 30, 45]);
+        assert_eq!(cubic.degree(), Some(3));
+        assert_eq!(cubic.value(6), BigInt::from(68));
+        assert_eq!(cubic.value(-1), BigInt::from(5));
+
+        let doubling = Polynomial::fit(&[1, 2, 4, 8]);
+        assert!(!doubling.confirmed);
+        assert_eq!(doubling.degree(), Some(3));
+        assert_eq!(doubling.value(4), BigInt::from(15));
+
+        let zero = Polynomial::fit(&[0, 0]);
+        assert_eq!(zero.degree(), None);
+        assert_eq!(zero.value(5), BigInt::from(0));
+    }
 }
@@ -1,182 +1,153 @@
-use std::{
-    collections::{HashMap, VecDeque},
-    fs,
-};
+use std::{collections::HashSet, env, fs};
 
-use aoc_2023::{grid_down, grid_find, grid_left, grid_right, grid_up, parse_grid};
+use aoc_2023::{grid_find, parse_grid};
 use itertools::Itertools;
 
 fn part1(input: &str) -> i64 {
-    let grid = parse_grid(input);
-
-    let start = grid_find(&grid, 'S').expect("Should have a start");
-    let mut queue = VecDeque::<(usize, usize)>::new();
-    let mut dist = HashMap::<(usize, usize), i64>::new();
-    dist.insert(start, 0);
-    if let Some(((ni, nj), c)) = grid_down(&grid, start) {
-        if ['|', 'L', 'J'].contains(&c) {
-            queue.push_back((ni, nj));
-            dist.insert((ni, nj), 1);
-        }
-    }
-    if let Some(((ni, nj), c)) = grid_up(&grid, start) {
-        if ['|', '7', 'F'].contains(&c) {
-            queue.push_back((ni, nj));
-            dist.insert((ni, nj), 1);
-        }
-    }
-    if let Some(((ni, nj), c)) = grid_left(&grid, start) {
-        if ['-', 'L', 'F'].contains(&c) {
-            queue.push_back((ni, nj));
-            dist.insert((ni, nj), 1);
-        }
-    }
-    if let Some(((ni, nj), c)) = grid_right(&grid, start) {
-        if ['-', 'J', '7'].contains(&c) {
-            queue.push_back((ni, nj));
-            dist.insert((ni, nj), 1);
-        }
-    }
+    let maze = PipeMaze::from(input);
 
-    while !queue.is_empty() {
-        let (i, j) = queue.pop_front().expect("Queue should not be empty");
-        let d = *dist.get(&(i, j)).expect("Should have a distance");
-        let neighbors = match grid[i][j] {
-            '|' => vec![grid_down(&grid, (i, j)), grid_up(&grid, (i, j))],
-            '-' => vec![grid_right(&grid, (i, j)), grid_left(&grid, (i, j))],
-            'L' => vec![grid_up(&grid, (i, j)), grid_right(&grid, (i, j))],
-            'J' => vec![grid_left(&grid, (i, j)), grid_up(&grid, (i, j))],
-            '7' => vec![grid_down(&grid, (i, j)), grid_left(&grid, (i, j))],
-            'F' => vec![grid_right(&grid, (i, j)), grid_down(&grid, (i, j))],
-            _ => panic!("Should be a valid pipe"),
-        };
-        let neighbors = neighbors
-            .iter()
-            .filter_map(|e| *e)
-            .filter(|((ni, nj), _)| !dist.contains_key(&(*ni, *nj)))
-            .collect_vec();
-        for ((ni, nj), _) in neighbors {
-            queue.push_back((ni, nj));
-            dist.insert((ni, nj), d + 1);
-        }
-    }
-
-    *dist.values().max().expect("Should have nodes")
+    (maze.path.len() / 2) as i64
 }
 
 fn part2(input: &str) -> i64 {
-    let mut grid = parse_grid(input);
-
-    let start = grid_find(&grid, 'S').expect("Should have a start");
-    let mut queue = VecDeque::<(usize, usize)>::new();
-    let mut dist = HashMap::<(usize, usize), i64>::new();
-    dist.insert(start, 0);
-    let mut start_neighbors = vec![];
-    if let Some(((ni, nj), c)) = grid_down(&grid, start) {
-        if ['|', 'L', 'J'].contains(&c) {
-            queue.push_back((ni, nj));
-            dist.insert((ni, nj), 1);
-            start_neighbors.push('d');
-        }
-    }
-    if let Some(((ni, nj), c)) = grid_up(&grid, start) {
-        if ['|', '7', 'F'].contains(&c) {
-            queue.push_back((ni, nj));
-            dist.insert((ni, nj), 1);
-            start_neighbors.push('u');
-        }
-    }
-    if let Some(((ni, nj), c)) = grid_left(&grid, start) {
-        if ['-', 'L', 'F'].contains(&c) {
-            queue.push_back((ni, nj));
-            dist.insert((ni, nj), 1);
-            start_neighbors.push('l');
-        }
-    }
-    if let Some(((ni, nj), c)) = grid_right(&grid, start) {
-        if ['-', 'J', '7'].contains(&c) {
-            queue.push_back((ni, nj));
-            dist.insert((ni, nj), 1);
-            start_neighbors.push('r');
-        }
-    }
+    let maze = PipeMaze::from(input);
 
-    start_neighbors.sort();
-    let behind_s = match start_neighbors
+    maze.classify()
         .iter()
-        .collect_tuple()
-        .expect("Should have 2 neighbors")
-    {
-        ('d', 'l') => '7',
-        ('d', 'r') => 'F',
-        ('d', 'u') => '|',
-        ('l', 'r') => '-',
-        ('l', 'u') => 'J',
-        ('r', 'u') => 'L',
-        _ => panic!("Should be a valid start"),
-    };
-    grid[start.0][start.1] = behind_s;
-
-    while !queue.is_empty() {
-        let (i, j) = queue.pop_front().expect("Queue should not be empty");
-        let d = *dist.get(&(i, j)).expect("Should have a distance");
-        let neighbors = match grid[i][j] {
-            '|' => vec![grid_down(&grid, (i, j)), grid_up(&grid, (i, j))],
-            '-' => vec![grid_right(&grid, (i, j)), grid_left(&grid, (i, j))],
-            'L' => vec![grid_up(&grid, (i, j)), grid_right(&grid, (i, j))],
-            'J' => vec![grid_left(&grid, (i, j)), grid_up(&grid, (i, j))],
-            '7' => vec![grid_down(&grid, (i, j)), grid_left(&grid, (i, j))],
-            'F' => vec![grid_right(&grid, (i, j)), grid_down(&grid, (i, j))],
-            _ => panic!("Should be a valid pipe"),
-        };
-        let neighbors = neighbors
-            .iter()
-            .filter_map(|e| *e)
-            .filter(|((ni, nj), _)| !dist.contains_key(&(*ni, *nj)))
+        .flatten()
+        .filter(|t| **t == Tile::Inside)
+        .count() as i64
+}
+
+const NORTH: (i64, i64) = (-1, 0);
+const SOUTH: (i64, i64) = (1, 0);
+const WEST: (i64, i64) = (0, -1);
+const EAST: (i64, i64) = (0, 1);
+
+/// Directions a pipe connects to.
+fn connections(c: char) -> Vec<(i64, i64)> {
+    match c {
+        '|' => vec![NORTH, SOUTH],
+        '-' => vec![WEST, EAST],
+        'L' => vec![NORTH, EAST],
+        'J' => vec![NORTH, WEST],
+        '7' => vec![SOUTH, WEST],
+        'F' => vec![SOUTH, EAST],
+        _ => vec![],
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Tile {
+    Loop,
+    Inside,
+    Outside,
+}
+
+/// A pipe grid and its loop through `S`.
+struct PipeMaze {
+    /// The grid, `S` replaced by its actual pipe.
+    grid: Vec<Vec<char>>,
+    start: (usize, usize),
+    /// Loop tiles in order, starting from `S`.
+    path: Vec<(usize, usize)>,
+}
+
+impl PipeMaze {
+    fn from(input: &str) -> PipeMaze {
+        let mut grid = parse_grid(input);
+        let start = grid_find(&grid, 'S').expect("Should have a start");
+
+        // `S` connects to the neighbors connecting back to it.
+        let start_connections = [NORTH, SOUTH, WEST, EAST]
+            .into_iter()
+            .filter(|&(di, dj)| {
+                step(&grid, start, (di, dj))
+                    .is_some_and(|(ni, nj)| connections(grid[ni][nj]).contains(&(-di, -dj)))
+            })
             .collect_vec();
-        for ((ni, nj), _) in neighbors {
-            queue.push_back((ni, nj));
-            dist.insert((ni, nj), d + 1);
+        grid[start.0][start.1] = "|-LJ7F"
+            .chars()
+            .find(|&c| connections(c) == start_connections)
+            .expect("Should have 2 neighbors");
+
+        let mut path = vec![start];
+        let mut direction = start_connections[0];
+        let mut position = step(&grid, start, direction).expect("Should be in the grid");
+        while position != start {
+            path.push(position);
+            let back = (-direction.0, -direction.1);
+            direction = *connections(grid[position.0][position.1])
+                .iter()
+                .find(|&&d| d != back)
+                .expect("Should be a pipe");
+            position = step(&grid, position, direction).expect("Should be in the grid");
         }
+
+        PipeMaze { grid, start, path }
     }
 
-    let mut res = 0;
-    let mut left_crossings = HashMap::<(usize, usize), (i64, char)>::new();
-    for i in 0..grid.len() {
-        for j in 0..grid[0].len() {
-            let new_crossings = match j {
-                0 => (0, '|'),
-                _ => {
-                    let ((li, lj), lc) =
-                        grid_left(&grid, (i, j)).expect("Should be a valid neighbor");
-                    let (prev_crossings, prev_open) = *left_crossings
-                        .get(&(li, lj))
-                        .expect("Should be a valid neighbor");
-                    if !dist.contains_key(&(li, lj)) {
-                        (prev_crossings, prev_open)
-                    } else {
-                        update_crossings(prev_crossings, prev_open, lc)
-                    }
-                }
-            };
-            left_crossings.insert((i, j), new_crossings);
-            if !dist.contains_key(&(i, j)) && new_crossings.0 % 2 == 1 {
-                res += 1;
-            }
-        }
+    /// Scan each line, counting the loop pipes going north on the left: inside if odd.
+    fn classify(&self) -> Vec<Vec<Tile>> {
+        let on_loop: HashSet<_> = self.path.iter().collect();
+        self.grid
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let mut crossings = 0;
+                line.iter()
+                    .enumerate()
+                    .map(|(j, &c)| {
+                        if on_loop.contains(&(i, j)) {
+                            if connections(c).contains(&NORTH) {
+                                crossings += 1;
+                            }
+                            Tile::Loop
+                        } else if crossings % 2 == 1 {
+                            Tile::Inside
+                        } else {
+                            Tile::Outside
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
     }
 
-    res
+    /// Draw the loop with box-drawing characters, `S` as is, and the other tiles as `I` or `O`.
+    fn render(&self) -> String {
+        self.classify()
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                line.iter()
+                    .enumerate()
+                    .map(|(j, tile)| match tile {
+                        _ if (i, j) == self.start => 'S',
+                        Tile::Loop => match self.grid[i][j] {
+                            '|' => '│',
+                            '-' => '─',
+                            'L' => '└',
+                            'J' => '┘',
+                            '7' => '┐',
+                            _ => '┌',
+                        },
+                        Tile::Inside => 'I',
+                        Tile::Outside => 'O',
+                    })
+                    .collect::<String>()
+            })
+            .join("\n")
+    }
 }
 
-fn update_crossings(prev_crossings: i64, prev_open: char, lc: char) -> (i64, char) {
-    match (prev_open, lc) {
-        (_, '|') => (prev_crossings + 1, '|'),
-        ('L', '7') => (prev_crossings + 1, '|'),
-        ('F', 'J') => (prev_crossings + 1, '|'),
-        (_, 'L') => (prev_crossings, 'L'),
-        (_, 'F') => (prev_crossings, 'F'),
-        _ => (prev_crossings, prev_open),
+fn step(grid: &[Vec<char>], position: (usize, usize), delta: (i64, i64)) -> Option<(usize, usize)> {
+    let i = position.0 as i64 + delta.0;
+    let j = position.1 as i64 + delta.1;
+    if i < 0 || j < 0 || i >= grid.len() as i64 || j >= grid[0].len() as i64 {
+        None
+    } else {
+        Some((i as usize, j as usize))
     }
 }
 
@@ -185,13 +156,19 @@ fn main() {
 
     let input = fs::read_to_string(file_path).expect("Should have been able to read the file");
 
+    // Show the loop with `day10 render`.
+    if env::args().nth(1).as_deref() == Some("render") {
+        println!("{}", PipeMaze::from(&input).render());
+        return;
+    }
+
     println!("{}", part1(&input));
     println!("{}", part2(&input));
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use super::{part1, part2, PipeMaze, Tile};
 
     #[test]
     fn test_part1() {
@@ -261,4 +238,28 @@ L7JLJL-JLJLJL--JLJ.L";
 
         assert_eq!(part2(input), 10);
     }
+
+    #[test]
+    fn test_pipe_maze() {
+        let input = "..F7.
+.FJ|.
+SJ.L7
+|F--J
+LJ...";
+        let maze = PipeMaze::from(input);
+
+        assert_eq!(maze.grid[2][0], 'F');
+        assert_eq!(maze.path.len(), 16);
+        assert_eq!(maze.path[..3], [(2, 0), (3, 0), (4, 0)]);
+        assert_eq!(maze.classify()[0][0], Tile::Outside);
+        assert_eq!(maze.classify()[2][2], Tile::Inside);
+        assert_eq!(
+            maze.render(),
+            "OO┌┐O
+O┌┘│O
+S┘I└┐
+│┌──┘
+└┘OOO"
+        );
+    }
 }
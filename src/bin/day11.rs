@@ -4,86 +4,90 @@ use aoc_2023::parse_grid;
 use itertools::Itertools;
 
 fn part1(input: &str) -> i64 {
-    let grid = parse_grid(input);
+    let universe = Universe::from(input);
 
-    let empty_columns = (0..grid[0].len())
-        .filter(|j| (0..grid.len()).map(|i| grid[i][*j]).all(|c| c != '#'))
-        .collect_vec();
-    let empty_lines = (0..grid.len())
-        .filter(|i| (0..grid[0].len()).map(|j| grid[*i][j]).all(|c| c != '#'))
-        .collect_vec();
-
-    let grid = duplicate_columns(&grid, &empty_columns);
-    let grid = duplicate_lines(&grid, &empty_lines);
-
-    let stars = (0..grid.len())
-        .cartesian_product(0..grid[0].len())
-        .filter(|(i, j)| grid[*i][*j] == '#')
-        .collect_vec();
-
-    stars
-        .iter()
-        .combinations(2)
-        .map(|v| {
-            let (s1, s2) = v.iter().collect_tuple().expect("Should be valid pairs");
-            distance(s1, s2)
-        })
-        .sum()
+    i64::try_from(universe.total_distance(2)).expect("Should fit in an i64")
 }
 
-fn distance(s1: &(usize, usize), s2: &(usize, usize)) -> i64 {
-    let (i1, j1) = s1;
-    let (i2, j2) = s2;
-    (*i1 as i64 - *i2 as i64).abs() + (*j1 as i64 - *j2 as i64).abs()
+/// Galaxies, with the number of empty lines and columns before each line and column.
+struct Universe {
+    galaxies: Vec<(usize, usize)>,
+    empty_lines_before: Vec<i64>,
+    empty_columns_before: Vec<i64>,
 }
 
-fn duplicate_columns(grid: &Vec<Vec<char>>, empty_columns: &[usize]) -> Vec<Vec<char>> {
-    let mut res: Vec<Vec<char>> = vec![];
-    for i in 0..grid.len() {
-        res.push(vec![]);
-        for j in 0..grid[0].len() {
-            res[i].push(grid[i][j]);
-            if empty_columns.contains(&j) {
-                res[i].push(grid[i][j]);
-            }
+impl Universe {
+    fn from(input: &str) -> Universe {
+        let grid = parse_grid(input);
+
+        let galaxies = (0..grid.len())
+            .cartesian_product(0..grid[0].len())
+            .filter(|(i, j)| grid[*i][*j] == '#')
+            .collect_vec();
+        let mut empty_lines = vec![true; grid.len()];
+        let mut empty_columns = vec![true; grid[0].len()];
+        for &(i, j) in &galaxies {
+            empty_lines[i] = false;
+            empty_columns[j] = false;
         }
-    }
-    res
-}
 
-fn duplicate_lines(grid: &[Vec<char>], empty_lines: &[usize]) -> Vec<Vec<char>> {
-    let mut res: Vec<Vec<char>> = vec![];
-    for (i, row) in grid.iter().enumerate() {
-        res.push(row.clone());
-        if empty_lines.contains(&i) {
-            res.push(row.clone());
+        Universe {
+            galaxies,
+            empty_lines_before: count_before(&empty_lines),
+            empty_columns_before: count_before(&empty_columns),
         }
     }
-    res
-}
 
-fn part2(input: &str, expansion: Option<i64>) -> i64 {
-    let expansion = expansion.unwrap_or(1000000);
-
-    let grid = parse_grid(input);
+    /// Position of a galaxy once each empty line and column is replaced by `expansion` of them.
+    /// In `i128`, as a large expansion times many empty lines overflows an `i64`.
+    fn expanded(&self, galaxy: (usize, usize), expansion: i64) -> (i128, i128) {
+        let (i, j) = galaxy;
+        let expansion = i128::from(expansion);
+        (
+            i as i128 + (expansion - 1) * i128::from(self.empty_lines_before[i]),
+            j as i128 + (expansion - 1) * i128::from(self.empty_columns_before[j]),
+        )
+    }
 
-    let stars = (0..grid.len())
-        .cartesian_product(0..grid[0].len())
-        .filter(|(i, j)| grid[*i][*j] == '#')
-        .collect_vec();
+    /// Sum of the distances between all pairs of galaxies, after expansion.
+    fn total_distance(&self, expansion: i64) -> i128 {
+        let (lines, columns): (Vec<_>, Vec<_>) = self
+            .galaxies
+            .iter()
+            .map(|&g| self.expanded(g, expansion))
+            .unzip();
+        axis_distance(lines) + axis_distance(columns)
+    }
+}
 
-    let dist = stars
+fn count_before(empty: &[bool]) -> Vec<i64> {
+    empty
         .iter()
-        .combinations(2)
-        .map(|v| {
-            let (s1, s2) = v.iter().collect_tuple().expect("Should be valid pairs");
-            distance(s1, s2)
+        .scan(0, |count, &e| {
+            let before = *count;
+            *count += i64::from(e);
+            Some(before)
         })
-        .sum::<i64>();
+        .collect()
+}
+
+/// Sum of |a - b| over all pairs: once sorted, each value is larger than all the previous ones.
+fn axis_distance(mut values: Vec<i128>) -> i128 {
+    values.sort();
+    let mut prefix = 0i128;
+    let mut res = 0i128;
+    for (k, &v) in values.iter().enumerate() {
+        res += v * k as i128 - prefix;
+        prefix += v;
+    }
+    res
+}
 
-    let dist_2 = part1(input);
+fn part2(input: &str, expansion: Option<i64>) -> i64 {
+    let expansion = expansion.unwrap_or(1000000);
+    let universe = Universe::from(input);
 
-    dist + (expansion - 1) * (dist_2 - dist)
+    i64::try_from(universe.total_distance(expansion)).expect("Should fit in an i64")
 }
 
 fn main() {
@@ -97,7 +101,7 @@ fn main() {
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use super::{part1, part2, Universe};
 
     #[test]
     fn test_part1() {
@@ -146,4 +150,31 @@ mod tests {
 
         assert_eq!(part2(input, Some(100)), 8410);
     }
+
+    #[test]
+    fn test_universe() {
+        let input = "...#......
+.......#..
+#.........
+..........
+......#...
+.#........
+.........#
+..........
+.......#..
+#...#.....";
+        let universe = Universe::from(input);
+
+        assert_eq!(universe.expanded((0, 3), 2), (0, 4));
+        assert_eq!(universe.expanded((9, 4), 2), (11, 5));
+        assert_eq!(universe.expanded((9, 4), 1000000), (2000007, 1000003));
+        assert_eq!(universe.total_distance(1), 292);
+        assert_eq!(universe.total_distance(i64::MAX / 10), 75631650702209161770);
+
+        let far = Universe::from(&format!("#\n{}#", ".\n".repeat(100)));
+        assert_eq!(
+            far.total_distance(i64::MAX),
+            101 + (i64::MAX as i128 - 1) * 100
+        );
+    }
 }
@@ -1,19 +1,43 @@
-use std::fs;
+use std::{env, fs, time::Instant};
 
+use aoc_2023::line_solver::Line;
 use cached::proc_macro::cached;
 use itertools::Itertools;
 
 struct Record {
     mask: String,
-    groups: Vec<i64>,
+    groups: Vec<usize>,
+}
+
+impl Record {
+    /// Number of ways to fill the `?`, the record being repeated `unfold` times, joined by `?`.
+    fn arrangements(&self, unfold: usize) -> i64 {
+        self.line(unfold)
+            .count()
+            .expect("Should have fewer arrangements than an i64 holds")
+    }
+
+    /// The line solver for the unfolded record, to list, sample or intersect the arrangements.
+    fn line(&self, unfold: usize) -> Line<'_> {
+        Line::unfolded(self.mask.as_bytes(), &self.groups, unfold)
+    }
+
+    /// The mask and groups repeated `factor` times, for the cached version.
+    fn unfolded(&self, factor: usize) -> (String, Vec<i64>) {
+        (
+            vec![self.mask.as_str(); factor].join("?"),
+            self.groups
+                .repeat(factor)
+                .iter()
+                .map(|&g| g as i64)
+                .collect(),
+        )
+    }
 }
 
 fn part1(input: &str) -> i64 {
     let records = input.lines().map(parse_line).collect_vec();
-    records
-        .iter()
-        .map(|record| combinations(record.mask.clone(), record.groups.clone()))
-        .sum()
+    records.iter().map(|record| record.arrangements(1)).sum()
 }
 
 fn parse_line(line: &str) -> Record {
@@ -24,7 +48,7 @@ fn parse_line(line: &str) -> Record {
     let mask = mask.to_string();
     let groups = groups
         .split(',')
-        .map(|e| e.parse::<i64>().expect("Should be a number"))
+        .map(|e| e.parse::<usize>().expect("Should be a number"))
         .collect_vec();
     Record { mask, groups }
 }
@@ -147,33 +171,25 @@ fn compute_groups(mask: &str) -> Vec<i64> {
 }
 
 fn part2(input: &str) -> i64 {
-    let records = input.lines().map(parse_line2).collect_vec();
-    records
-        .iter()
-        .map(|record| combinations(record.mask.clone(), record.groups.clone()))
-        .sum()
+    let records = input.lines().map(parse_line).collect_vec();
+    records.iter().map(|record| record.arrangements(5)).sum()
 }
 
-fn parse_line2(line: &str) -> Record {
-    let rec = parse_line(line);
-    let mask5 = format!(
-        "{}?{}?{}?{}?{}",
-        rec.mask, rec.mask, rec.mask, rec.mask, rec.mask
-    );
-    let groups5 = [
-        rec.groups.clone(),
-        rec.groups.clone(),
-        rec.groups.clone(),
-        rec.groups.clone(),
-        rec.groups,
-    ]
-    .iter()
-    .flatten()
-    .cloned()
-    .collect_vec();
-    Record {
-        mask: mask5,
-        groups: groups5,
+/// Compare with the previous, cached, recursion.
+fn bench(input: &str) {
+    let records = input.lines().map(parse_line).collect_vec();
+    for unfold in [1, 5] {
+        let now = Instant::now();
+        let count: i64 = records.iter().map(|r| r.arrangements(unfold)).sum();
+        println!("dp x{}: {} in {:?}", unfold, count, now.elapsed());
+
+        let now = Instant::now();
+        let count: i64 = records
+            .iter()
+            .map(|r| r.unfolded(unfold))
+            .map(|(mask, groups)| combinations(mask, groups))
+            .sum();
+        println!("cached x{}: {} in {:?}", unfold, count, now.elapsed());
     }
 }
 
@@ -182,13 +198,20 @@ fn main() {
 
     let input = fs::read_to_string(file_path).expect("Should have been able to read the file");
 
+    if env::args().nth(1).as_deref() == Some("bench") {
+        bench(&input);
+        return;
+    }
+
     println!("{}", part1(&input));
     println!("{}", part2(&input));
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{combinations, parse_line, part1, part2};
 
     #[test]
     fn test_part1() {
@@ -220,4 +243,55 @@ mod tests {
 
         assert_eq!(part2(input), 525152);
     }
+
+    #[test]
+    fn test_arrangements() {
+        let input = "???.### 1,1,3
+.??..??...?##. 1,1,3
+?#?#?#?#?#?#?#? 1,3,1,6
+????.#...#... 4,1,1
+????.######..#####. 1,6,5
+?###???????? 3,2,1
+# 1
+?# 2
+#?? 2,1";
+
+        for (line, (once, five)) in input.lines().zip([
+            (1, 1),
+            (4, 16384),
+            (1, 1),
+            (1, 16),
+            (4, 2500),
+            (10, 506250),
+            (1, 1),
+            (1, 1),
+            (0, 0),
+        ]) {
+            let record = parse_line(line);
+            assert_eq!(record.arrangements(1), once, "{}", line);
+            assert_eq!(record.arrangements(5), five, "{}", line);
+            let (mask, groups) = record.unfolded(5);
+            assert_eq!(combinations(mask, groups), five);
+        }
+    }
+
+    #[test]
+    fn test_line() {
+        let record = parse_line("?###???????? 3,2,1");
+        let line = record.line(1);
+        let arrangements = line.arrangements().collect::<Vec<_>>();
+        assert_eq!(arrangements.len() as i64, record.arrangements(1));
+        assert!(arrangements.contains(&b".###.##.#...".to_vec()));
+
+        let mut rng = StdRng::seed_from_u64(12);
+        let sample = line.sample(&mut rng).expect("Should have an arrangement");
+        assert!(arrangements.contains(&sample));
+
+        assert_eq!(line.forced_cells(), vec![(0, b'.'), (4, b'.')]);
+        let sample = record
+            .line(5)
+            .sample(&mut rng)
+            .expect("Should have an arrangement");
+        assert_eq!(sample.len(), 64);
+    }
 }
@@ -8,98 +8,77 @@ fn part1(input: &str) -> i64 {
     let sections = input.split("\n\n");
     let grids = sections.map(parse_grid).collect_vec();
 
-    grids.iter().map(reflection_summary).sum()
+    grids
+        .iter()
+        .flat_map(|g| reflections(g, 0))
+        .map(|r| r.score())
+        .sum()
 }
 
-fn reflection_summary(grid: &Vec<Vec<char>>) -> i64 {
-    let mut res = 0;
-
-    for (i, _) in grid.iter().enumerate() {
-        if i == grid.len() - 1 {
-            break;
-        }
-        let mut k = 0i64;
-        let mut sym = true;
-        let i = i as i64;
-        while i - k >= 0 && i + k + 1 < grid.len() as i64 {
-            if grid[(i - k) as usize] != grid[(i + k + 1) as usize] {
-                sym = false;
-                break;
-            }
-            k += 1;
-        }
-        if sym {
-            res += 100 * (i + 1);
-        }
-    }
-
-    for (j, _) in grid[0].iter().enumerate() {
-        if j == grid[0].len() - 1 {
-            break;
-        }
-        let mut k = 0i64;
-        let mut sym = true;
-        let j = j as i64;
-        while j - k >= 0 && j + k + 1 < grid[0].len() as i64 {
-            if (0..=grid.len() - 1)
-                .any(|i| grid[i][(j - k) as usize] != grid[i][(j + k + 1) as usize])
-            {
-                sym = false;
-                break;
-            }
-            k += 1;
-        }
-        if sym {
-            res += j + 1;
-        }
-    }
-
-    res
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Axis {
+    Row,
+    Column,
 }
 
-fn reflection_summary2(grid: &Vec<Vec<char>>) -> i64 {
-    let mut res = 0;
+/// A mirror between lines (or columns) `index - 1` and `index`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Reflection {
+    axis: Axis,
+    index: usize,
+    /// Cells to flip for a perfect reflection, taken before the mirror.
+    smudges: Vec<(usize, usize)>,
+}
 
-    for (i, _) in grid.iter().enumerate() {
-        if i == grid.len() - 1 {
-            break;
-        }
-        let mut k = 0i64;
-        let mut sym = 0;
-        let i = i as i64;
-        while i - k >= 0 && i + k + 1 < grid.len() as i64 {
-            for (j, _) in grid[0].iter().enumerate() {
-                if grid[(i - k) as usize][j] != grid[(i + k + 1) as usize][j] {
-                    sym += 1;
-                }
-            }
-            k += 1;
-        }
-        if sym == 1 {
-            res += 100 * (i + 1);
+impl Reflection {
+    fn score(&self) -> i64 {
+        match self.axis {
+            Axis::Row => 100 * self.index as i64,
+            Axis::Column => self.index as i64,
         }
     }
+}
 
-    for (j, _) in grid[0].iter().enumerate() {
-        if j == grid[0].len() - 1 {
-            break;
-        }
-        let mut k = 0i64;
-        let mut sym = 0;
-        let j = j as i64;
-        while j - k >= 0 && j + k + 1 < grid[0].len() as i64 {
-            for (i, _) in grid.iter().enumerate() {
-                if grid[i][(j - k) as usize] != grid[i][(j + k + 1) as usize] {
-                    sym += 1;
-                }
+/// Every reflection with exactly `smudges` differing cells.
+fn reflections(grid: &[Vec<char>], smudges: usize) -> Vec<Reflection> {
+    let (n, m) = (grid.len(), grid[0].len());
+    assert!(n <= 64 && m <= 64, "Should fit in a u64 mask");
+    let rows: Vec<u64> = (0..n)
+        .map(|i| (0..m).filter(|&j| grid[i][j] == '#').map(|j| 1 << j).sum())
+        .collect();
+    let columns: Vec<u64> = (0..m)
+        .map(|j| (0..n).filter(|&i| grid[i][j] == '#').map(|i| 1 << i).sum())
+        .collect();
+
+    let mut res = vec![];
+    for (axis, lines) in [(Axis::Row, rows), (Axis::Column, columns)] {
+        for index in 1..lines.len() {
+            let pairs = (0..index).rev().zip(index..lines.len());
+            let differences = pairs
+                .clone()
+                .map(|(a, b)| (lines[a] ^ lines[b]).count_ones() as usize)
+                .sum::<usize>();
+            if differences != smudges {
+                continue;
             }
-            k += 1;
-        }
-        if sym == 1 {
-            res += j + 1;
+            let smudges = pairs
+                .flat_map(|(a, b)| {
+                    let diff = lines[a] ^ lines[b];
+                    (0..64)
+                        .filter(move |k| diff & (1 << k) != 0)
+                        .map(move |k| match axis {
+                            Axis::Row => (a, k),
+                            Axis::Column => (k, a),
+                        })
+                })
+                .collect();
+            res.push(Reflection {
+                axis,
+                index,
+                smudges,
+            });
         }
     }
-
     res
 }
 
@@ -108,7 +87,11 @@ fn part2(input: &str) -> i64 {
     let sections = input.split("\n\n");
     let grids = sections.map(parse_grid).collect_vec();
 
-    grids.iter().map(reflection_summary2).sum()
+    grids
+        .iter()
+        .flat_map(|g| reflections(g, 1))
+        .map(|r| r.score())
+        .sum()
 }
 
 fn main() {
@@ -122,7 +105,9 @@ fn main() {
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use aoc_2023::parse_grid;
+
+    use super::{part1, part2, reflections, Axis, Reflection};
 
     #[test]
     fn test_part1() {
@@ -165,4 +150,34 @@ mod tests {
 
         assert_eq!(part2(input), 400);
     }
+
+    #[test]
+    fn test_reflections() {
+        let input = "#.##..##.
+..#.##.#.
+##......#
+##......#
+..#.##.#.
+..##..##.
+#.#.##.#.";
+        let grid = parse_grid(input);
+
+        assert_eq!(
+            reflections(&grid, 0),
+            vec![Reflection {
+                axis: Axis::Column,
+                index: 5,
+                smudges: vec![],
+            }]
+        );
+        assert_eq!(
+            reflections(&grid, 1),
+            vec![Reflection {
+                axis: Axis::Row,
+                index: 3,
+                smudges: vec![(0, 0)],
+            }]
+        );
+        assert_eq!(reflections(&grid, 2).len(), 1);
+    }
 }
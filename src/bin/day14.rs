@@ -3,185 +3,166 @@ use std::{collections::HashMap, fs};
 use aoc_2023::parse_grid;
 
 fn part1(input: &str) -> i64 {
-    let grid = parse_grid(input);
+    let mut platform = Platform::from(input);
 
-    let new_grid = move_north(&grid);
-    compute_load(&new_grid)
+    platform.tilt(Direction::North);
+    platform.load(Direction::North)
 }
 
-fn part2(input: &str) -> i64 {
-    let grid = parse_grid(input);
-
-    let mut cache = HashMap::new();
-    cache.insert(grid.clone(), 0);
-    let mut new_grid = cycle(&grid);
-    let mut i = 1i64;
-    while !cache.contains_key(&new_grid) {
-        cache.insert(new_grid.clone(), i);
-        new_grid = cycle(&new_grid);
-        i += 1;
-    }
-
-    let cycle_start = cache.get(&new_grid).expect("Should be in cache");
-    let cycle_len = i - cycle_start;
-    let remaining = (1000000000 - cycle_start) % cycle_len;
-
-    for _ in 0..remaining {
-        new_grid = cycle(&new_grid);
-    }
+fn part2(input: &str, cycles: Option<usize>) -> i64 {
+    let cycles = cycles.unwrap_or(1000000000);
+    let mut platform = Platform::from(input);
 
-    compute_load(&new_grid)
+    platform.run(&parse_tilts("NWSE"), cycles);
+    platform.load(Direction::North)
 }
 
-fn compute_load(grid: &Vec<Vec<char>>) -> i64 {
-    let mut res = 0;
-    for (j, _) in grid[0].iter().enumerate() {
-        for (i, _) in grid.iter().enumerate() {
-            match grid[i][j] {
-                'O' => {
-                    res += (grid.len() - i) as i64;
-                }
-                '#' => {}
-                '.' => {}
-                _ => panic!("Should be a valid cell"),
-            }
-        }
-    }
-    res
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    North,
+    West,
+    South,
+    East,
 }
 
-fn move_north(grid: &[Vec<char>]) -> Vec<Vec<char>> {
-    let mut new_grid = grid.to_owned();
-
-    for (j, _) in grid[0].iter().enumerate() {
-        let mut destination = 0;
-        for (i, _) in grid.iter().enumerate() {
-            match grid[i][j] {
-                'O' => {
-                    if destination != i {
-                        new_grid[destination][j] = 'O';
-                        new_grid[i][j] = '.';
-                    }
-                    destination += 1;
-                }
-                '#' => {
-                    new_grid[i][j] = '#';
-                    destination = i + 1;
-                }
-                '.' => {
-                    new_grid[i][j] = '.';
-                }
-                _ => panic!("Should be a valid cell"),
-            }
-        }
-    }
+fn parse_tilts(s: &str) -> Vec<Direction> {
+    s.chars()
+        .map(|c| match c {
+            'N' => Direction::North,
+            'W' => Direction::West,
+            'S' => Direction::South,
+            'E' => Direction::East,
+            _ => panic!("Should be a valid direction"),
+        })
+        .collect()
+}
 
-    new_grid
+/// Rocks as one bitset per line, bit `j` being column `j`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Platform {
+    height: usize,
+    width: usize,
+    round: Vec<u128>,
+    cube: Vec<u128>,
 }
 
-fn cycle(grid: &[Vec<char>]) -> Vec<Vec<char>> {
-    let mut new_grid = grid.to_owned();
-
-    for (j, _) in grid[0].iter().enumerate() {
-        let mut destination = 0;
-        for (i, _) in grid.iter().enumerate() {
-            match grid[i][j] {
-                'O' => {
-                    if destination != i {
-                        new_grid[destination][j] = 'O';
-                        new_grid[i][j] = '.';
-                    }
-                    destination += 1;
-                }
-                '#' => {
-                    new_grid[i][j] = '#';
-                    destination = i + 1;
-                }
-                '.' => {
-                    new_grid[i][j] = '.';
-                }
-                _ => panic!("Should be a valid cell"),
-            }
-        }
-    }
+impl Platform {
+    fn from(input: &str) -> Platform {
+        let grid = parse_grid(input);
+        let (height, width) = (grid.len(), grid[0].len());
+        assert!(width <= 128, "Should fit in a u128");
+        let bits = |rock: char| {
+            grid.iter()
+                .map(|line| {
+                    line.iter()
+                        .enumerate()
+                        .filter(|(_, &c)| c == rock)
+                        .fold(0u128, |acc, (j, _)| acc | (1 << j))
+                })
+                .collect::<Vec<_>>()
+        };
 
-    let grid = new_grid.clone();
-    for (i, _) in grid.iter().enumerate() {
-        let mut destination = 0;
-        for (j, _) in grid[0].iter().enumerate() {
-            match grid[i][j] {
-                'O' => {
-                    if destination != j {
-                        new_grid[i][destination] = 'O';
-                        new_grid[i][j] = '.';
-                    }
-                    destination += 1;
-                }
-                '#' => {
-                    new_grid[i][j] = '#';
-                    destination = j + 1;
-                }
-                '.' => {
-                    new_grid[i][j] = '.';
-                }
-                _ => panic!("Should be a valid cell"),
-            }
+        Platform {
+            height,
+            width,
+            round: bits('O'),
+            cube: bits('#'),
         }
     }
 
-    let grid = new_grid.clone();
-    for (j, _) in grid[0].iter().enumerate() {
-        let mut destination = grid.len() - 1;
-        for (i, _) in grid.iter().enumerate().rev() {
-            match grid[i][j] {
-                'O' => {
-                    if destination != i {
-                        new_grid[destination][j] = 'O';
-                        new_grid[i][j] = '.';
+    /// Roll every round rock one step at a time, until none moves.
+    fn tilt(&mut self, direction: Direction) {
+        match direction {
+            Direction::North | Direction::South => {
+                let (from, to): (Vec<usize>, Vec<usize>) = if direction == Direction::North {
+                    ((1..self.height).collect(), (0..self.height - 1).collect())
+                } else {
+                    (
+                        (0..self.height - 1).rev().collect(),
+                        (1..self.height).rev().collect(),
+                    )
+                };
+                let mut moved = true;
+                while moved {
+                    moved = false;
+                    for (&i, &k) in from.iter().zip(&to) {
+                        let movable = self.round[i] & !(self.round[k] | self.cube[k]);
+                        if movable != 0 {
+                            self.round[i] &= !movable;
+                            self.round[k] |= movable;
+                            moved = true;
+                        }
                     }
-                    destination -= 1;
                 }
-                '#' => {
-                    new_grid[i][j] = '#';
-                    if i > 0 {
-                        destination = i - 1;
+            }
+            Direction::West | Direction::East => {
+                let mask = if self.width == 128 {
+                    u128::MAX
+                } else {
+                    (1 << self.width) - 1
+                };
+                for (round, cube) in self.round.iter_mut().zip(&self.cube) {
+                    loop {
+                        let free = !(*round | cube) & mask;
+                        let movable = if direction == Direction::West {
+                            *round & (free << 1)
+                        } else {
+                            *round & (free >> 1)
+                        };
+                        if movable == 0 {
+                            break;
+                        }
+                        *round &= !movable;
+                        *round |= if direction == Direction::West {
+                            movable >> 1
+                        } else {
+                            movable << 1
+                        };
                     }
                 }
-                '.' => {
-                    new_grid[i][j] = '.';
-                }
-                _ => panic!("Should be a valid cell"),
             }
         }
     }
 
-    let grid = new_grid.clone();
-    for (i, _) in grid.iter().enumerate() {
-        let mut destination = grid[0].len() - 1;
-        for (j, _) in grid[0].iter().enumerate().rev() {
-            match grid[i][j] {
-                'O' => {
-                    if destination != j {
-                        new_grid[i][destination] = 'O';
-                        new_grid[i][j] = '.';
-                    }
-                    destination -= 1;
-                }
-                '#' => {
-                    new_grid[i][j] = '#';
-                    if j > 0 {
-                        destination = j - 1;
+    /// Apply the sequence of tilts `times` times, skipping ahead once the rocks loop.
+    fn run(&mut self, sequence: &[Direction], times: usize) {
+        let mut seen = HashMap::from([(self.round.clone(), 0)]);
+        let mut i = 0;
+        while i < times {
+            for &direction in sequence {
+                self.tilt(direction);
+            }
+            i += 1;
+            if let Some(start) = seen.insert(self.round.clone(), i) {
+                let remaining = (times - i) % (i - start);
+                for _ in 0..remaining {
+                    for &direction in sequence {
+                        self.tilt(direction);
                     }
                 }
-                '.' => {
-                    new_grid[i][j] = '.';
-                }
-                _ => panic!("Should be a valid cell"),
+                return;
             }
         }
     }
 
-    new_grid
+    /// Sum of the distances of the round rocks to the opposite side, plus one.
+    fn load(&self, side: Direction) -> i64 {
+        self.round
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &line)| {
+                (0..self.width)
+                    .filter(move |j| line & (1 << j) != 0)
+                    .map(move |j| (i, j))
+            })
+            .map(|(i, j)| match side {
+                Direction::North => self.height - i,
+                Direction::South => i + 1,
+                Direction::West => self.width - j,
+                Direction::East => j + 1,
+            } as i64)
+            .sum()
+    }
 }
 
 fn main() {
@@ -190,12 +171,12 @@ fn main() {
     let input = fs::read_to_string(file_path).expect("Should have been able to read the file");
 
     println!("{}", part1(&input));
-    println!("{}", part2(&input));
+    println!("{}", part2(&input, None));
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use super::{parse_tilts, part1, part2, Direction, Platform};
 
     #[test]
     fn test_part1() {
@@ -226,6 +207,51 @@ O.#..O.#.#
 #....###..
 #OO..#....";
 
-        assert_eq!(part2(input), 64);
+        assert_eq!(part2(input, None), 64);
+    }
+
+    #[test]
+    fn test_platform() {
+        let input = "O....#....
+O.OO#....#
+.....##...
+OO.#O....O
+.O.....O#.
+O.#..O.#.#
+..O..#O..O
+.......O..
+#....###..
+#OO..#....";
+        let mut platform = Platform::from(input);
+        let mut cycled = platform.clone();
+
+        // After 1 cycle, from the puzzle.
+        cycled.run(&parse_tilts("NWSE"), 1);
+        assert_eq!(
+            cycled,
+            Platform::from(
+                ".....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#...."
+            )
+        );
+
+        platform.run(&parse_tilts("NWSE"), 3);
+        assert_eq!(platform.load(Direction::North), 69);
+
+        let mut tilted = Platform::from("O.#.O\n.....\n.O..O");
+        tilted.tilt(Direction::East);
+        assert_eq!(tilted, Platform::from(".O#.O\n.....\n...OO"));
+        assert_eq!(tilted.load(Direction::East), 2 + 5 + 4 + 5);
+        tilted.run(&parse_tilts("SW"), 2);
+        assert_eq!(tilted, Platform::from("..#..\nO....\nOOO.."));
+        assert_eq!(tilted.load(Direction::South), 2 + 3 + 3 + 3);
     }
 }
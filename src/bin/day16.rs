@@ -1,8 +1,12 @@
-use std::{collections::HashSet, fs};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs, thread,
+};
 
 use aoc_2023::{grid_down, grid_left, grid_right, grid_up, parse_grid};
+use itertools::Itertools;
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 enum Direction {
     Up,
     Down,
@@ -10,64 +14,307 @@ enum Direction {
     Right,
 }
 
+impl Direction {
+    fn arrow(self) -> char {
+        match self {
+            Direction::Up => '^',
+            Direction::Down => 'v',
+            Direction::Left => '<',
+            Direction::Right => '>',
+        }
+    }
+}
+
 fn part1(input: &str) -> i64 {
     let grid = parse_grid(input);
+    let trace = Trace::from(&grid, ((0, 0), Direction::Right));
 
-    let position = (0, 0);
-    let direction = Direction::Right;
-    let mut energized = HashSet::new();
-    let mut cache = HashSet::new();
-    dfs(&grid, position, direction, &mut energized, &mut cache);
-
-    energized.len() as i64
+    trace.energized().len() as i64
 }
 
 fn part2(input: &str) -> i64 {
     let grid = parse_grid(input);
+    let graph = BeamGraph::from(&grid);
 
-    let left = (0..grid.len())
-        .map(|i| energize(&grid, (i, 0), Direction::Right))
-        .max()
-        .expect("Should have a max");
-    let right = (0..grid.len())
-        .map(|i| energize(&grid, (i, grid[0].len() - 1), Direction::Left))
-        .max()
-        .expect("Should have a max");
-    let top = (0..grid[0].len())
-        .map(|j| energize(&grid, (0, j), Direction::Down))
-        .max()
-        .expect("Should have a max");
-    let bottom = (0..grid[0].len())
-        .map(|j| energize(&grid, (grid.len() - 1, j), Direction::Up))
-        .max()
-        .expect("Should have a max");
-
-    *[left, right, top, bottom]
-        .iter()
-        .max()
-        .expect("Should have a max")
+    graph.max_energized(4) as i64
 }
 
-fn dfs(
-    grid: &Vec<Vec<char>>,
-    position: (usize, usize),
-    direction: Direction,
-    energized: &mut HashSet<(usize, usize)>,
-    cache: &mut HashSet<((usize, usize), Direction)>,
-) {
-    if cache.contains(&(position, direction)) {
-        return;
+type Beam = ((usize, usize), Direction);
+
+/// Tiles crossed by a beam, with the directions it moved through each of them.
+struct Trace {
+    height: usize,
+    width: usize,
+    directions: HashMap<(usize, usize), Vec<Direction>>,
+}
+
+impl Trace {
+    /// Follow the beam with an explicit stack.
+    fn from(grid: &[Vec<char>], beam: Beam) -> Trace {
+        let mut directions: HashMap<(usize, usize), Vec<Direction>> = HashMap::new();
+        let mut stack = vec![beam];
+        while let Some((position, direction)) = stack.pop() {
+            let seen = directions.entry(position).or_default();
+            if seen.contains(&direction) {
+                continue;
+            }
+            seen.push(direction);
+            stack.extend(next(grid, position, direction));
+        }
+
+        Trace {
+            height: grid.len(),
+            width: grid[0].len(),
+            directions,
+        }
+    }
+
+    fn energized(&self) -> HashSet<(usize, usize)> {
+        self.directions.keys().copied().collect()
+    }
+
+    /// Energized tiles as `#`, the others as `.`.
+    fn render_energized(&self) -> String {
+        (0..self.height)
+            .map(|i| {
+                (0..self.width)
+                    .map(|j| {
+                        if self.directions.contains_key(&(i, j)) {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .join("\n")
     }
 
-    cache.insert((position, direction));
-    energized.insert(position);
-    for (new_position, new_direction) in next(grid, position, direction) {
-        dfs(grid, new_position, new_direction, energized, cache);
+    /// The grid with the beam drawn on empty tiles: its direction, or how many beams crossed.
+    fn render_arrows(&self, grid: &[Vec<char>]) -> String {
+        grid.iter()
+            .enumerate()
+            .map(|(i, line)| {
+                line.iter()
+                    .enumerate()
+                    .map(|(j, &c)| match self.directions.get(&(i, j)) {
+                        Some(directions) if c == '.' && directions.len() == 1 => {
+                            directions[0].arrow()
+                        }
+                        Some(directions) if c == '.' => {
+                            char::from_digit(directions.len() as u32, 10)
+                                .expect("Should be a digit")
+                        }
+                        _ => c,
+                    })
+                    .collect::<String>()
+            })
+            .join("\n")
     }
 }
 
+/// Beams as a graph of straight segments, each ending on a mirror, a splitter or the border.
+/// Segments in the same strongly connected component energize the same tiles, so the tiles
+/// are computed once per component, from the components downstream.
+struct BeamGraph {
+    height: usize,
+    width: usize,
+    /// Segment starting with each beam.
+    segments: HashMap<Beam, usize>,
+    /// Component of each segment.
+    component: Vec<usize>,
+    /// Tiles energized from each component, as bitsets.
+    energized: Vec<Vec<u64>>,
+}
+
+impl BeamGraph {
+    fn from(grid: &[Vec<char>]) -> BeamGraph {
+        let (height, width) = (grid.len(), grid[0].len());
+        let words = (height * width).div_ceil(64);
+
+        // Walk the segments reachable from the border.
+        let mut segments = HashMap::new();
+        let mut tiles: Vec<Vec<u64>> = vec![];
+        let mut edges: Vec<Vec<usize>> = vec![];
+        let mut to_visit = border_beams(height, width);
+        for beam in &to_visit {
+            segments.insert(*beam, segments.len());
+        }
+        let mut i = 0;
+        while i < to_visit.len() {
+            let (mut position, direction) = to_visit[i];
+            let mut segment_tiles = vec![0; words];
+            let mut successors = vec![];
+            loop {
+                set_bit(&mut segment_tiles, position.0 * width + position.1);
+                let next_beams = next(grid, position, direction);
+                match next_beams[..] {
+                    [(next_position, next_direction)] if next_direction == direction => {
+                        position = next_position;
+                    }
+                    _ => {
+                        for beam in next_beams {
+                            let index = *segments.entry(beam).or_insert_with(|| {
+                                to_visit.push(beam);
+                                to_visit.len() - 1
+                            });
+                            successors.push(index);
+                        }
+                        break;
+                    }
+                }
+            }
+            tiles.push(segment_tiles);
+            edges.push(successors);
+            i += 1;
+        }
+
+        // Components come out downstream first.
+        let components = tarjan(&edges);
+        let mut component = vec![0; edges.len()];
+        for (c, members) in components.iter().enumerate() {
+            for &segment in members {
+                component[segment] = c;
+            }
+        }
+        let mut energized: Vec<Vec<u64>> = Vec::with_capacity(components.len());
+        for members in &components {
+            let mut bits = vec![0; words];
+            for &segment in members {
+                or_bits(&mut bits, &tiles[segment]);
+                for &successor in &edges[segment] {
+                    if component[successor] != energized.len() {
+                        or_bits(&mut bits, &energized[component[successor]]);
+                    }
+                }
+            }
+            energized.push(bits);
+        }
+
+        BeamGraph {
+            height,
+            width,
+            segments,
+            component,
+            energized,
+        }
+    }
+
+    /// Number of tiles energized by a beam entering the grid.
+    fn energized(&self, beam: Beam) -> usize {
+        let segment = self.segments[&beam];
+        self.energized[self.component[segment]]
+            .iter()
+            .map(|w| w.count_ones() as usize)
+            .sum()
+    }
+
+    /// Best number of energized tiles from the border, scanned with several threads.
+    fn max_energized(&self, threads: usize) -> usize {
+        let beams = border_beams(self.height, self.width);
+        let chunk_size = beams.len().div_ceil(threads.max(1));
+        thread::scope(|scope| {
+            let handles = beams
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || chunk.iter().map(|&beam| self.energized(beam)).max())
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .filter_map(|h| h.join().expect("Should not panic"))
+                .max()
+                .expect("Should have a max")
+        })
+    }
+}
+
+/// Beams entering the grid from each border tile.
+fn border_beams(height: usize, width: usize) -> Vec<Beam> {
+    let mut res = vec![];
+    for i in 0..height {
+        res.push(((i, 0), Direction::Right));
+        res.push(((i, width - 1), Direction::Left));
+    }
+    for j in 0..width {
+        res.push(((0, j), Direction::Down));
+        res.push(((height - 1, j), Direction::Up));
+    }
+    res
+}
+
+fn set_bit(bits: &mut [u64], i: usize) {
+    bits[i / 64] |= 1 << (i % 64);
+}
+
+fn or_bits(bits: &mut [u64], other: &[u64]) {
+    for (w, o) in bits.iter_mut().zip(other) {
+        *w |= o;
+    }
+}
+
+/// Tarjan's strongly connected components, without recursion.
+/// Components are listed in reverse topological order.
+fn tarjan(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let n = edges.len();
+    let mut index = vec![usize::MAX; n];
+    let mut low = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = vec![];
+    let mut components = vec![];
+    let mut counter = 0;
+
+    for root in 0..n {
+        if index[root] != usize::MAX {
+            continue;
+        }
+        // (node, next edge to explore)
+        let mut call_stack = vec![(root, 0)];
+        index[root] = counter;
+        low[root] = counter;
+        counter += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some(&mut (node, ref mut next_edge)) = call_stack.last_mut() {
+            if let Some(&successor) = edges[node].get(*next_edge) {
+                *next_edge += 1;
+                if index[successor] == usize::MAX {
+                    index[successor] = counter;
+                    low[successor] = counter;
+                    counter += 1;
+                    stack.push(successor);
+                    on_stack[successor] = true;
+                    call_stack.push((successor, 0));
+                } else if on_stack[successor] {
+                    low[node] = low[node].min(index[successor]);
+                }
+                continue;
+            }
+
+            call_stack.pop();
+            if let Some(&(parent, _)) = call_stack.last() {
+                low[parent] = low[parent].min(low[node]);
+            }
+            if low[node] == index[node] {
+                let mut component = vec![];
+                loop {
+                    let member = stack.pop().expect("Should be on the stack");
+                    on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+    components
+}
+
 fn next(
-    grid: &Vec<Vec<char>>,
+    grid: &[Vec<char>],
     position: (usize, usize),
     direction: Direction,
 ) -> Vec<((usize, usize), Direction)> {
@@ -106,25 +353,29 @@ fn next(
         .collect()
 }
 
-fn energize(grid: &Vec<Vec<char>>, position: (usize, usize), direction: Direction) -> i64 {
-    let mut energized = HashSet::new();
-    let mut cache = HashSet::new();
-    dfs(grid, position, direction, &mut energized, &mut cache);
-    energized.len() as i64
-}
-
 fn main() {
     let file_path = "data/day16_input.txt";
 
     let input = fs::read_to_string(file_path).expect("Should have been able to read the file");
 
+    // Debug a layout with `day16 render`.
+    if env::args().nth(1).as_deref() == Some("render") {
+        let grid = parse_grid(&input);
+        let trace = Trace::from(&grid, ((0, 0), Direction::Right));
+        println!("{}\n", trace.render_arrows(&grid));
+        println!("{}", trace.render_energized());
+        return;
+    }
+
     println!("{}", part1(&input));
     println!("{}", part2(&input));
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use aoc_2023::parse_grid;
+
+    use super::{part1, part2, tarjan, BeamGraph, Direction, Trace};
 
     #[test]
     fn test_part1() {
@@ -157,4 +408,84 @@ mod tests {
 
         assert_eq!(part2(input), 51);
     }
+
+    #[test]
+    fn test_beam_graph() {
+        let input = r".|...\....
+|.-.\.....
+.....|-...
+........|.
+..........
+.........\
+..../.\\..
+.-.-/..|..
+.|....-|.\
+..//.|....";
+        let grid = parse_grid(input);
+        let graph = BeamGraph::from(&grid);
+
+        assert_eq!(graph.energized(((0, 0), Direction::Right)), 46);
+        assert_eq!(graph.energized(((0, 3), Direction::Down)), 51);
+        for threads in 1..5 {
+            assert_eq!(graph.max_energized(threads), 51);
+        }
+    }
+
+    #[test]
+    fn test_tarjan() {
+        let edges = vec![vec![1], vec![2, 3], vec![0], vec![4], vec![3], vec![]];
+
+        let components = tarjan(&edges)
+            .into_iter()
+            .map(|mut c| {
+                c.sort();
+                c
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(components, vec![vec![3, 4], vec![0, 1, 2], vec![5]]);
+    }
+
+    #[test]
+    fn test_trace() {
+        let input = r".|...\....
+|.-.\.....
+.....|-...
+........|.
+..........
+.........\
+..../.\\..
+.-.-/..|..
+.|....-|.\
+..//.|....";
+        let grid = parse_grid(input);
+        let trace = Trace::from(&grid, ((0, 0), Direction::Right));
+
+        assert_eq!(trace.energized().len(), 46);
+        assert_eq!(
+            trace.render_energized(),
+            "######....
+.#...#....
+.#...#####
+.#...##...
+.#...##...
+.#...##...
+.#..####..
+########..
+.#######..
+.#...#.#.."
+        );
+        assert_eq!(
+            trace.render_arrows(&grid),
+            r">|<<<\....
+|v-.\^....
+.v...|->>>
+.v...v^.|.
+.v...v^...
+.v...v^..\
+.v../2\\..
+<->-/vv|..
+.|<<<2-|.\
+.v//.|.v.."
+        );
+    }
 }
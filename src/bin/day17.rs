@@ -1,4 +1,4 @@
-use std::fs;
+use std::{env, fs, time::Instant};
 
 // https://doc.rust-lang.org/std/collections/binary_heap/index.html
 
@@ -35,7 +35,7 @@ impl PartialOrd for State {
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Hash)]
 enum Direction {
     Up,
     Down,
@@ -43,6 +43,42 @@ enum Direction {
     Right,
 }
 
+impl Direction {
+    const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
+    fn step(self, grid: &[Vec<u8>], position: (usize, usize)) -> Option<((usize, usize), u8)> {
+        match self {
+            Direction::Up => grid_up(grid, position),
+            Direction::Down => grid_down(grid, position),
+            Direction::Left => grid_left(grid, position),
+            Direction::Right => grid_right(grid, position),
+        }
+    }
+
+    fn arrow(self) -> char {
+        match self {
+            Direction::Up => '^',
+            Direction::Down => 'v',
+            Direction::Left => '<',
+            Direction::Right => '>',
+        }
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
 struct Node {
     position: (usize, usize),
@@ -50,7 +86,28 @@ struct Node {
     streak: u8,
 }
 
-type NeighborFn = fn(&Vec<Vec<u8>>, &Node) -> Vec<(Node, u8)>;
+/// Movement rules of a crucible.
+#[derive(Copy, Clone, Debug)]
+struct Crucible {
+    /// Blocks to move in a straight line after starting or turning, before turning or stopping.
+    min_streak: u8,
+    /// Maximum blocks in a straight line.
+    max_streak: u8,
+    /// Extra heat loss for each turn.
+    turn_cost: usize,
+}
+
+const CRUCIBLE: Crucible = Crucible {
+    min_streak: 1,
+    max_streak: 3,
+    turn_cost: 0,
+};
+
+const ULTRA_CRUCIBLE: Crucible = Crucible {
+    min_streak: 4,
+    max_streak: 10,
+    turn_cost: 0,
+};
 
 // Dijkstra's shortest path algorithm.
 
@@ -58,14 +115,21 @@ type NeighborFn = fn(&Vec<Vec<u8>>, &Node) -> Vec<(Node, u8)>;
 // to each node. This implementation isn't memory-efficient as it may leave duplicate
 // nodes in the queue. It also uses `usize::MAX` as a sentinel value,
 // for a simpler implementation.
+// Returns the heat loss and every block of the path, `start` included.
 fn shortest_path(
-    grid: &Vec<Vec<u8>>,
+    grid: &[Vec<u8>],
     start: (usize, usize),
     goal: (usize, usize),
-    neighbors: NeighborFn,
-) -> Option<usize> {
+    crucible: Crucible,
+) -> Option<(usize, Vec<(usize, usize)>)> {
+    assert!(
+        1 <= crucible.min_streak && crucible.min_streak <= crucible.max_streak,
+        "Should have valid streaks"
+    );
     // dist[node] = current shortest distance from `start` to `node`
     let mut dist = HashMap::new();
+    // prev[node] = previous node on the current shortest path to `node`
+    let mut prev = HashMap::new();
 
     let mut heap = BinaryHeap::new();
 
@@ -81,26 +145,23 @@ fn shortest_path(
         node: start_node,
     });
 
-    let mut res = None;
     // Examine the frontier with lower cost nodes first (min-heap)
     while let Some(State { cost, node }) = heap.pop() {
-        if node.position == goal {
-            res = match res {
-                None => Some(cost),
-                Some(x) => Some(x.min(cost)),
-            };
-        }
-
         // Important as we may have already found a better way
         if cost > *dist.get(&node).unwrap_or(&usize::MAX) {
             continue;
         }
 
+        // The first time we reach the goal is the best one
+        if node.position == goal {
+            return Some((cost, build_path(&prev, node)));
+        }
+
         // For each node we can reach, see if we can find a way with
         // a lower cost going through this node
-        for (neighbor, neighbor_cost) in neighbors(grid, &node) {
+        for (neighbor, neighbor_cost) in neighbors(grid, crucible, &node) {
             let next = State {
-                cost: cost + neighbor_cost as usize,
+                cost: cost + neighbor_cost,
                 node: neighbor,
             };
 
@@ -109,240 +170,189 @@ fn shortest_path(
                 heap.push(next);
                 // Relaxation, we have now found a better way
                 dist.insert(next.node, next.cost);
+                prev.insert(next.node, node);
             }
         }
     }
 
-    res
+    None
 }
 
-fn neighbors1(grid: &Vec<Vec<u8>>, node: &Node) -> Vec<(Node, u8)> {
-    let mut res = vec![];
-
-    if node.direction != Some(Direction::Down)
-        && (node.direction != Some(Direction::Up) || node.streak != 3)
-    {
-        let up = grid_up(grid, node.position);
-        if let Some((position, cost)) = up {
-            res.push((
-                Node {
-                    position,
-                    direction: Some(Direction::Up),
-                    streak: if node.direction == Some(Direction::Up) {
-                        node.streak + 1
-                    } else {
-                        1
-                    },
-                },
-                cost,
-            ));
-        }
+/// Expand the chain of nodes ending at `end` into blocks.
+fn build_path(prev: &HashMap<Node, Node>, end: Node) -> Vec<(usize, usize)> {
+    let mut corners = vec![end.position];
+    let mut node = end;
+    while let Some(&previous) = prev.get(&node) {
+        corners.push(previous.position);
+        node = previous;
     }
+    corners.reverse();
+    join_runs(&corners)
+}
 
-    if node.direction != Some(Direction::Up)
-        && (node.direction != Some(Direction::Down) || node.streak != 3)
-    {
-        let down = grid_down(grid, node.position);
-        if let Some((position, cost)) = down {
-            res.push((
-                Node {
-                    position,
-                    direction: Some(Direction::Down),
-                    streak: if node.direction == Some(Direction::Down) {
-                        node.streak + 1
-                    } else {
-                        1
-                    },
-                },
-                cost,
-            ));
+/// Blocks of the straight runs between consecutive positions, the first one included.
+fn join_runs(corners: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut res = corners[..1].to_vec();
+    for w in corners.windows(2) {
+        let (mut i, mut j) = w[0];
+        while (i, j) != w[1] {
+            match (w[1].0.cmp(&i), w[1].1.cmp(&j)) {
+                (Ordering::Less, _) => i -= 1,
+                (Ordering::Greater, _) => i += 1,
+                (_, Ordering::Less) => j -= 1,
+                _ => j += 1,
+            }
+            res.push((i, j));
         }
     }
+    res
+}
 
-    if node.direction != Some(Direction::Right)
-        && (node.direction != Some(Direction::Left) || node.streak != 3)
-    {
-        let left = grid_left(grid, node.position);
-        if let Some((position, cost)) = left {
-            res.push((
-                Node {
-                    position,
-                    direction: Some(Direction::Left),
-                    streak: if node.direction == Some(Direction::Left) {
-                        node.streak + 1
-                    } else {
-                        1
-                    },
-                },
-                cost,
-            ));
-        }
-    }
+/// Search engine for the crucible paths.
+#[derive(Copy, Clone, Debug)]
+enum Engine {
+    /// Dijkstra with a binary heap, on (position, direction, streak) states.
+    Dijkstra,
+    /// Dial's bucket queue, on (position, axis) states.
+    Dial,
+}
 
-    if node.direction != Some(Direction::Left)
-        && (node.direction != Some(Direction::Right) || node.streak != 3)
-    {
-        let right = grid_right(grid, node.position);
-        if let Some((position, cost)) = right {
-            res.push((
-                Node {
-                    position,
-                    direction: Some(Direction::Right),
-                    streak: if node.direction == Some(Direction::Right) {
-                        node.streak + 1
-                    } else {
-                        1
-                    },
-                },
-                cost,
-            ));
+impl Engine {
+    fn shortest_path(
+        self,
+        grid: &[Vec<u8>],
+        start: (usize, usize),
+        goal: (usize, usize),
+        crucible: Crucible,
+    ) -> Option<(usize, Vec<(usize, usize)>)> {
+        match self {
+            Engine::Dijkstra => shortest_path(grid, start, goal, crucible),
+            Engine::Dial => shortest_path_dial(grid, start, goal, crucible),
         }
     }
-
-    res
 }
 
-fn neighbors2(grid: &Vec<Vec<u8>>, node: &Node) -> Vec<(Node, u8)> {
-    let mut res = vec![];
+// Dial's algorithm: heat losses are small integers, so the priority queue can be
+// a circular array of buckets, one per cost.
+// A move is a whole straight run followed by a turn, so a state only needs the axis of the
+// last run: the next one is on the other axis. Every move pays `turn_cost`, the first one
+// excluded by removing it at the end.
+fn shortest_path_dial(
+    grid: &[Vec<u8>],
+    start: (usize, usize),
+    goal: (usize, usize),
+    crucible: Crucible,
+) -> Option<(usize, Vec<(usize, usize)>)> {
+    assert!(
+        1 <= crucible.min_streak && crucible.min_streak <= crucible.max_streak,
+        "Should have valid streaks"
+    );
+    if start == goal {
+        return Some((0, vec![start]));
+    }
+    let m = grid[0].len();
+    // Axis 0 after a vertical run, 1 after a horizontal one.
+    let index = |(i, j): (usize, usize), axis: usize| (i * m + j) * 2 + axis;
+    let position = |state: usize| ((state / 2) / m, (state / 2) % m);
+
+    let mut dist = vec![usize::MAX; grid.len() * m * 2];
+    let mut prev = vec![usize::MAX; grid.len() * m * 2];
+    let max_move = 9 * crucible.max_streak as usize + crucible.turn_cost;
+    let mut buckets = vec![vec![]; max_move + 1];
+    let slots = buckets.len();
+    let mut pending = 0;
+    for axis in 0..2 {
+        dist[index(start, axis)] = 0;
+        buckets[0].push(index(start, axis));
+        pending += 1;
+    }
 
-    if node.direction != Some(Direction::Down)
-        && (node.direction != Some(Direction::Up) || node.streak != 10)
-    {
-        let mut steps = 4;
-        if node.direction == Some(Direction::Up) {
-            steps = 1;
-        }
-        let mut valid = true;
-        let mut cost = 0;
-        let mut position = node.position;
-        for _ in 0..steps {
-            let up = grid_up(grid, position);
-            if let Some((next_position, c)) = up {
-                cost += c;
-                position = next_position;
-            } else {
-                valid = false;
-                break;
+    let mut cost = 0;
+    while pending > 0 {
+        let slot = cost % slots;
+        while let Some(state) = buckets[slot].pop() {
+            pending -= 1;
+            if dist[state] != cost {
+                continue;
+            }
+            if position(state) == goal {
+                let mut corners = vec![goal];
+                let mut state = state;
+                while prev[state] != usize::MAX {
+                    state = prev[state];
+                    corners.push(position(state));
+                }
+                corners.reverse();
+                return Some((cost - crucible.turn_cost, join_runs(&corners)));
             }
-        }
-        if valid {
-            res.push((
-                Node {
-                    position,
-                    direction: Some(Direction::Up),
-                    streak: if node.direction == Some(Direction::Up) {
-                        node.streak + steps as u8
-                    } else {
-                        steps as u8
-                    },
-                },
-                cost,
-            ));
-        }
-    }
 
-    if node.direction != Some(Direction::Up)
-        && (node.direction != Some(Direction::Down) || node.streak != 10)
-    {
-        let mut steps = 4;
-        if node.direction == Some(Direction::Down) {
-            steps = 1;
-        }
-        let mut valid = true;
-        let mut cost = 0;
-        let mut position = node.position;
-        for _ in 0..steps {
-            let down = grid_down(grid, position);
-            if let Some((next_position, c)) = down {
-                cost += c;
-                position = next_position;
+            let axis = state % 2;
+            let directions = if axis == 0 {
+                [Direction::Left, Direction::Right]
             } else {
-                valid = false;
-                break;
+                [Direction::Up, Direction::Down]
+            };
+            for direction in directions {
+                let mut current = position(state);
+                let mut next_cost = cost + crucible.turn_cost;
+                for streak in 1..=crucible.max_streak {
+                    let Some((next, c)) = direction.step(grid, current) else {
+                        break;
+                    };
+                    current = next;
+                    next_cost += c as usize;
+                    if streak < crucible.min_streak {
+                        continue;
+                    }
+                    let next_state = index(next, 1 - axis);
+                    if next_cost < dist[next_state] {
+                        dist[next_state] = next_cost;
+                        prev[next_state] = state;
+                        buckets[next_cost % slots].push(next_state);
+                        pending += 1;
+                    }
+                }
             }
         }
-        if valid {
-            res.push((
-                Node {
-                    position,
-                    direction: Some(Direction::Down),
-                    streak: if node.direction == Some(Direction::Down) {
-                        node.streak + steps as u8
-                    } else {
-                        steps as u8
-                    },
-                },
-                cost,
-            ));
-        }
+        cost += 1;
     }
 
-    if node.direction != Some(Direction::Right)
-        && (node.direction != Some(Direction::Left) || node.streak != 10)
-    {
-        let mut steps = 4;
-        if node.direction == Some(Direction::Left) {
-            steps = 1;
-        }
-        let mut valid = true;
-        let mut cost = 0;
-        let mut position = node.position;
-        for _ in 0..steps {
-            let left = grid_left(grid, position);
-            if let Some((next_position, c)) = left {
-                cost += c;
-                position = next_position;
-            } else {
-                valid = false;
-                break;
-            }
+    None
+}
+
+/// Nodes reachable in one move, a turn or start going `min_streak` blocks at once.
+fn neighbors(grid: &[Vec<u8>], crucible: Crucible, node: &Node) -> Vec<(Node, usize)> {
+    let mut res = vec![];
+
+    for direction in Direction::ALL {
+        if node.direction == Some(direction.opposite()) {
+            continue;
         }
-        if valid {
-            res.push((
-                Node {
-                    position,
-                    direction: Some(Direction::Left),
-                    streak: if node.direction == Some(Direction::Left) {
-                        node.streak + steps as u8
-                    } else {
-                        steps as u8
-                    },
-                },
-                cost,
-            ));
+        let straight = node.direction == Some(direction);
+        if straight && node.streak >= crucible.max_streak {
+            continue;
         }
-    }
+        let (steps, streak, mut cost) = match node.direction {
+            Some(_) if straight => (1, node.streak + 1, 0),
+            Some(_) => (crucible.min_streak, crucible.min_streak, crucible.turn_cost),
+            None => (crucible.min_streak, crucible.min_streak, 0),
+        };
 
-    if node.direction != Some(Direction::Left)
-        && (node.direction != Some(Direction::Right) || node.streak != 10)
-    {
-        let mut steps = 4;
-        if node.direction == Some(Direction::Right) {
-            steps = 1;
-        }
-        let mut valid = true;
-        let mut cost = 0;
-        let mut position = node.position;
+        let mut position = Some(node.position);
         for _ in 0..steps {
-            let right = grid_right(grid, position);
-            if let Some((next_position, c)) = right {
-                cost += c;
-                position = next_position;
-            } else {
-                valid = false;
-                break;
-            }
+            position = position
+                .and_then(|p| direction.step(grid, p))
+                .map(|(p, c)| {
+                    cost += c as usize;
+                    p
+                });
         }
-        if valid {
+        if let Some(position) = position {
             res.push((
                 Node {
                     position,
-                    direction: Some(Direction::Right),
-                    streak: if node.direction == Some(Direction::Right) {
-                        node.streak + steps as u8
-                    } else {
-                        steps as u8
-                    },
+                    direction: Some(direction),
+                    streak,
                 },
                 cost,
             ));
@@ -352,13 +362,47 @@ fn neighbors2(grid: &Vec<Vec<u8>>, node: &Node) -> Vec<(Node, u8)> {
     res
 }
 
-fn part1(input: &str) -> i64 {
+/// Draw the path over the grid with the direction it enters each block.
+fn render_path(grid: &[Vec<u8>], path: &[(usize, usize)]) -> String {
+    let mut canvas = grid
+        .iter()
+        .map(|line| {
+            line.iter()
+                .map(|c| char::from_digit(*c as u32, 10).expect("Should be a digit"))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    for w in path.windows(2) {
+        let ((i1, j1), (i2, j2)) = (w[0], w[1]);
+        let direction = match (i2.cmp(&i1), j2.cmp(&j1)) {
+            (Ordering::Less, _) => Direction::Up,
+            (Ordering::Greater, _) => Direction::Down,
+            (_, Ordering::Less) => Direction::Left,
+            _ => Direction::Right,
+        };
+        canvas[i2][j2] = direction.arrow();
+    }
+    canvas
+        .iter()
+        .map(|line| line.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn minimal_heat_loss(input: &str, crucible: Crucible, engine: Engine) -> i64 {
     let grid = parse_grid(input);
 
     let start = (0, 0);
     let goal = (grid.len() - 1, grid[0].len() - 1);
 
-    shortest_path(&grid, start, goal, neighbors1).expect("Should have a path") as i64
+    let (cost, _) = engine
+        .shortest_path(&grid, start, goal, crucible)
+        .expect("Should have a path");
+    cost as i64
+}
+
+fn part1(input: &str) -> i64 {
+    minimal_heat_loss(input, CRUCIBLE, Engine::Dial)
 }
 
 fn parse_grid(input: &str) -> Vec<Vec<u8>> {
@@ -373,12 +417,43 @@ fn parse_grid(input: &str) -> Vec<Vec<u8>> {
 }
 
 fn part2(input: &str) -> i64 {
-    let grid = parse_grid(input);
+    minimal_heat_loss(input, ULTRA_CRUCIBLE, Engine::Dial)
+}
 
-    let start = (0, 0);
-    let goal = (grid.len() - 1, grid[0].len() - 1);
+/// Run a custom crucible with `day17 <min_streak> <max_streak> [turn_cost] [dijkstra|dial]`.
+fn parse_args(args: &[String]) -> Option<(Crucible, Engine)> {
+    let (min_streak, max_streak, rest) = match args {
+        [min_streak, max_streak, rest @ ..] if rest.len() <= 2 => (min_streak, max_streak, rest),
+        _ => return None,
+    };
+    let crucible = Crucible {
+        min_streak: min_streak.parse().ok()?,
+        max_streak: max_streak.parse().ok()?,
+        turn_cost: rest.first().map_or(Some(0), |c| c.parse().ok())?,
+    };
+    let engine = match rest.get(1).map(String::as_str) {
+        None | Some("dial") => Engine::Dial,
+        Some("dijkstra") => Engine::Dijkstra,
+        Some(_) => return None,
+    };
+    Some((crucible, engine))
+}
 
-    shortest_path(&grid, start, goal, neighbors2).expect("Should have a path") as i64
+/// Time both engines on both crucibles.
+fn bench(input: &str) {
+    for crucible in [CRUCIBLE, ULTRA_CRUCIBLE] {
+        for engine in [Engine::Dijkstra, Engine::Dial] {
+            let now = Instant::now();
+            let cost = minimal_heat_loss(input, crucible, engine);
+            println!(
+                "{:?} {:?}: {} in {:?}",
+                engine,
+                crucible,
+                cost,
+                now.elapsed()
+            );
+        }
+    }
 }
 
 fn main() {
@@ -386,13 +461,36 @@ fn main() {
 
     let input = fs::read_to_string(file_path).expect("Should have been able to read the file");
 
-    println!("{}", part1(&input));
-    println!("{}", part2(&input));
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.is_empty() {
+        println!("{}", part1(&input));
+        println!("{}", part2(&input));
+        return;
+    }
+    if args == ["bench"] {
+        bench(&input);
+        return;
+    }
+
+    let (crucible, engine) = parse_args(&args)
+        .expect("Usage: day17 [bench | <min_streak> <max_streak> [turn_cost] [dijkstra|dial]]");
+    let grid = parse_grid(&input);
+    let goal = (grid.len() - 1, grid[0].len() - 1);
+    match engine.shortest_path(&grid, (0, 0), goal, crucible) {
+        Some((cost, path)) => {
+            println!("{}", render_path(&grid, &path));
+            println!("{}", cost);
+        }
+        None => println!("No path for {:?}", crucible),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use super::{
+        parse_args, parse_grid, part1, part2, render_path, shortest_path, Crucible, Engine,
+        CRUCIBLE, ULTRA_CRUCIBLE,
+    };
 
     #[test]
     fn test_part1() {
@@ -443,4 +541,112 @@ mod tests {
 
         assert_eq!(part2(input), 71);
     }
+
+    #[test]
+    fn test_shortest_path() {
+        let input = "2413432311323
+3215453535623
+3255245654254
+3446585845452
+4546657867536
+1438598798454
+4457876987766
+3637877979653
+4654967986887
+4564679986453
+1224686865563
+2546548887735
+4322674655533";
+        let grid = parse_grid(input);
+
+        let (cost, path) = shortest_path(&grid, (0, 0), (12, 12), CRUCIBLE).unwrap();
+        assert_eq!(cost, 102);
+        assert_eq!(
+            path[1..]
+                .iter()
+                .map(|&(i, j)| grid[i][j] as usize)
+                .sum::<usize>(),
+            102
+        );
+        assert_eq!(
+            render_path(&grid, &path),
+            "2>>34^>>>1323
+32v>>>35v5623
+32552456v>>54
+3446585845v52
+4546657867v>6
+14385987984v4
+44578769877v6
+36378779796v>
+465496798688v
+456467998645v
+12246868655<v
+25465488877v5
+43226746555v>"
+        );
+    }
+
+    #[test]
+    fn test_turn_cost() {
+        let input = "111111111111
+999999999991
+999999999991
+999999999991
+999999999991";
+        let grid = parse_grid(input);
+        let crucible = Crucible {
+            min_streak: 1,
+            max_streak: 12,
+            turn_cost: 100,
+        };
+
+        let (cost, path) = shortest_path(&grid, (0, 0), (4, 11), crucible).unwrap();
+        assert_eq!(cost, 115);
+        assert_eq!(path.len(), 16);
+        assert_eq!(
+            Engine::Dial.shortest_path(&grid, (0, 0), (4, 11), crucible),
+            Some((cost, path))
+        );
+        assert!(parse_args(&["4".to_string(), "10".to_string()]).is_some());
+        assert!(parse_args(&["4".to_string()]).is_none());
+    }
+
+    #[test]
+    fn test_engines() {
+        let input = "2413432311323
+3215453535623
+3255245654254
+3446585845452
+4546657867536
+1438598798454
+4457876987766
+3637877979653
+4654967986887
+4564679986453
+1224686865563
+2546548887735
+4322674655533";
+        let grid = parse_grid(input);
+
+        for crucible in [CRUCIBLE, ULTRA_CRUCIBLE] {
+            let (dijkstra, _) = Engine::Dijkstra
+                .shortest_path(&grid, (0, 0), (12, 12), crucible)
+                .unwrap();
+            let (dial, path) = Engine::Dial
+                .shortest_path(&grid, (0, 0), (12, 12), crucible)
+                .unwrap();
+            assert_eq!(dial, dijkstra);
+            assert_eq!(
+                path[1..]
+                    .iter()
+                    .map(|&(i, j)| grid[i][j] as usize)
+                    .sum::<usize>(),
+                dial
+            );
+        }
+        assert_eq!(
+            Engine::Dial.shortest_path(&grid, (3, 3), (3, 3), CRUCIBLE),
+            Some((0, vec![(3, 3)]))
+        );
+    }
 }
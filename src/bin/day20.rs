@@ -100,11 +100,8 @@ struct Pulse {
 
 fn part1(input: &str) -> i64 {
     let modules = input.lines().map(parse_module).collect_vec();
-    let mut modules: HashMap<String, Module> = modules
-        .iter()
-        .cloned()
-        .map(|m| (m.name(), m.clone()))
-        .collect();
+    let mut modules: HashMap<String, Module> =
+        modules.iter().map(|m| (m.name(), m.clone())).collect();
 
     for m in modules.clone().values() {
         for d in m.dest() {
@@ -201,11 +198,8 @@ fn lcm(a: i64, b: i64) -> i64 {
 
 fn part2(input: &str) -> i64 {
     let modules = input.lines().map(parse_module).collect_vec();
-    let mut modules: HashMap<String, Module> = modules
-        .iter()
-        .cloned()
-        .map(|m| (m.name(), m.clone()))
-        .collect();
+    let mut modules: HashMap<String, Module> =
+        modules.iter().map(|m| (m.name(), m.clone())).collect();
 
     for m in modules.clone().values() {
         for d in m.dest() {
@@ -1,9 +1,10 @@
 use std::{
     collections::{HashMap, HashSet, VecDeque},
-    fs,
+    env, fs,
 };
 
 use aoc_2023::{grid_down, grid_find, grid_left, grid_right, grid_up, parse_grid};
+use itertools::Itertools;
 
 fn part1(input: &str, steps: Option<i64>) -> i64 {
     let steps = steps.unwrap_or(64);
@@ -23,7 +24,7 @@ fn part1(input: &str, steps: Option<i64>) -> i64 {
     positions.len() as i64
 }
 
-fn get_neighbors(grid: &Vec<Vec<char>>, position: (usize, usize)) -> Vec<(usize, usize)> {
+fn get_neighbors(grid: &[Vec<char>], position: (usize, usize)) -> Vec<(usize, usize)> {
     let mut res = vec![];
     for (neighbor, c) in [
         grid_down(grid, position),
@@ -41,7 +42,7 @@ fn get_neighbors(grid: &Vec<Vec<char>>, position: (usize, usize)) -> Vec<(usize,
     res
 }
 
-fn get_neighbors2(grid: &Vec<Vec<char>>, position: (i64, i64)) -> Vec<(i64, i64)> {
+fn get_neighbors2(grid: &[Vec<char>], position: (i64, i64)) -> Vec<(i64, i64)> {
     let mut res = vec![];
     let (i, j) = position;
     let n = grid.len() as i64;
@@ -62,6 +63,7 @@ fn get_neighbors2(grid: &Vec<Vec<char>>, position: (i64, i64)) -> Vec<(i64, i64)
     res
 }
 
+#[cfg(test)]
 fn part2_small(input: &str, steps: i64) -> i64 {
     let grid = parse_grid(input);
 
@@ -99,37 +101,188 @@ fn part2_small(input: &str, steps: i64) -> i64 {
 
 fn part2(input: &str, steps: Option<i64>) -> i64 {
     let steps = steps.unwrap_or(26501365);
-    // Unfortunately, we have to choose between slow and specialized.
-    if steps <= 5000 {
-        return part2_small(input, steps);
-    }
     let grid = parse_grid(input);
 
-    let start = grid_find(&grid, 'S').expect("Should have a start");
-    let mut dist = HashMap::new();
-    let mut queue = VecDeque::new();
-    queue.push_back((start, 0));
-    dist.insert(start, 0);
-    while !queue.is_empty() {
-        let (position, d) = queue.pop_front().expect("Should have a position");
-        for neighbor in get_neighbors(&grid, position) {
-            if dist.contains_key(&neighbor) {
-                continue;
+    reachable_infinite(&grid, steps).expect("Should extrapolate the reachable plots")
+}
+
+/// BFS on the infinite garden, one distance layer at a time.
+struct InfiniteBfs<'a> {
+    grid: &'a [Vec<char>],
+    seen: HashSet<(i64, i64)>,
+    frontier: Vec<(i64, i64)>,
+    /// Number of plots at each distance.
+    counts: Vec<i64>,
+}
+
+impl<'a> InfiniteBfs<'a> {
+    fn new(grid: &'a [Vec<char>]) -> InfiniteBfs<'a> {
+        let start = grid_find(grid, 'S').expect("Should have a start");
+        let start = (start.0 as i64, start.1 as i64);
+        InfiniteBfs {
+            grid,
+            seen: HashSet::from([start]),
+            frontier: vec![start],
+            counts: vec![1],
+        }
+    }
+
+    fn extend_to(&mut self, distance: usize) {
+        while self.counts.len() <= distance {
+            let mut next = vec![];
+            for position in &self.frontier {
+                for neighbor in get_neighbors2(self.grid, *position) {
+                    if self.seen.insert(neighbor) {
+                        next.push(neighbor);
+                    }
+                }
             }
-            dist.insert(neighbor, d + 1);
-            queue.push_back((neighbor, d + 1));
+            self.counts.push(next.len() as i64);
+            self.frontier = next;
+        }
+    }
+
+    /// Plots reachable in exactly `steps` steps.
+    fn reachable(&mut self, steps: usize) -> i64 {
+        self.extend_to(steps);
+        self.counts[..=steps].iter().rev().step_by(2).sum()
+    }
+}
+
+/// Past a few tiles, the number of reachable plots grows quadratically with each period.
+/// Fit that quadratic, verify it on more points and extrapolate.
+fn reachable_infinite(grid: &[Vec<char>], steps: i64) -> Result<i64, String> {
+    const MAX_PERIODS: usize = 10;
+    let steps = usize::try_from(steps).map_err(|_| format!("Negative step count {}", steps))?;
+    let mut period = num::integer::lcm(grid.len(), grid[0].len());
+    // Keep the parity of the step count.
+    if period % 2 == 1 {
+        period *= 2;
+    }
+    let rest = steps % period;
+    let mut bfs = InfiniteBfs::new(grid);
+
+    for k0 in 0..MAX_PERIODS {
+        // 3 points for the fit, 2 to verify it.
+        let ks = k0..k0 + 5;
+        if steps <= rest + (ks.end - 1) * period {
+            return Ok(bfs.reachable(steps));
+        }
+        let values = ks.map(|k| bfs.reachable(rest + k * period)).collect_vec();
+        let d1 = values.windows(2).map(|w| w[1] - w[0]).collect_vec();
+        let d2 = d1.windows(2).map(|w| w[1] - w[0]).collect_vec();
+        if d2.iter().all_equal() {
+            // Newton's forward differences.
+            let x = ((steps - rest) / period - k0) as i64;
+            return Ok(values[0] + x * d1[0] + x * (x - 1) / 2 * d2[0]);
+        }
+    }
+    Err(format!(
+        "Growth is not quadratic after {} periods of {} steps",
+        MAX_PERIODS, period
+    ))
+}
+
+/// BFS distances of the reachable plots, up to `radius` tiles away from the start tile.
+/// Paths leaving that area are ignored.
+struct DistanceMap<'a> {
+    grid: &'a [Vec<char>],
+    radius: i64,
+    distances: HashMap<(i64, i64), usize>,
+}
+
+#[derive(Debug, PartialEq)]
+struct TileStats {
+    odd: usize,
+    even: usize,
+    max_distance: Option<usize>,
+    /// Plots outside the diamond inscribed in the tile, around its own centre. On the start
+    /// tile, those are the corners part 2 counts.
+    odd_corners: usize,
+    even_corners: usize,
+}
+
+impl<'a> DistanceMap<'a> {
+    fn new(grid: &'a [Vec<char>], radius: i64) -> DistanceMap<'a> {
+        let start = grid_find(grid, 'S').expect("Should have a start");
+        let start = (start.0 as i64, start.1 as i64);
+        let (n, m) = (grid.len() as i64, grid[0].len() as i64);
+        let rows = (-radius * n)..((radius + 1) * n);
+        let columns = (-radius * m)..((radius + 1) * m);
+
+        let mut distances = HashMap::from([(start, 0)]);
+        let mut queue = VecDeque::from([(start, 0)]);
+        while let Some((position, d)) = queue.pop_front() {
+            for neighbor in get_neighbors2(grid, position) {
+                if !rows.contains(&neighbor.0)
+                    || !columns.contains(&neighbor.1)
+                    || distances.contains_key(&neighbor)
+                {
+                    continue;
+                }
+                distances.insert(neighbor, d + 1);
+                queue.push_back((neighbor, d + 1));
+            }
+        }
+
+        DistanceMap {
+            grid,
+            radius,
+            distances,
         }
     }
 
-    // https://github.com/villuna/aoc23/wiki/A-Geometric-solution-to-advent-of-code-2023,-day-21
-    let odd = dist.values().filter(|d| *d % 2 == 1).count() as i64;
-    let even = dist.values().filter(|d| *d % 2 == 0).count() as i64;
-    let max = grid.len() / 2;
-    let odd_corners = dist.values().filter(|d| *d % 2 == 1 && **d > max).count() as i64;
-    let even_corners = dist.values().filter(|d| *d % 2 == 0 && **d > max).count() as i64;
-    let n = steps / (grid.len() as i64);
+    /// Distance of a plot, in the coordinates of the start tile extended to the others.
+    fn distance(&self, position: (i64, i64)) -> Option<usize> {
+        self.distances.get(&position).copied()
+    }
 
-    (n + 1) * (n + 1) * odd + n * n * even - (n + 1) * odd_corners + n * even_corners
+    fn tile_stats(&self, tile: (i64, i64)) -> TileStats {
+        let (n, m) = (self.grid.len() as i64, self.grid[0].len() as i64);
+        let (half_n, half_m) = (n / 2, m / 2);
+        let mut stats = TileStats {
+            odd: 0,
+            even: 0,
+            max_distance: None,
+            odd_corners: 0,
+            even_corners: 0,
+        };
+        for (i, j) in (0..n).cartesian_product(0..m) {
+            let Some(d) = self.distance((tile.0 * n + i, tile.1 * m + j)) else {
+                continue;
+            };
+            let corner =
+                (i - half_n).abs() * half_m + (j - half_m).abs() * half_n > half_n * half_m;
+            if d % 2 == 1 {
+                stats.odd += 1;
+                stats.odd_corners += usize::from(corner);
+            } else {
+                stats.even += 1;
+                stats.even_corners += usize::from(corner);
+            }
+            stats.max_distance = stats.max_distance.max(Some(d));
+        }
+        stats
+    }
+
+    /// Draw rocks as `#`, plots at an odd distance as `O`, even as `E` and unreachable as `.`.
+    fn render(&self) -> String {
+        let (n, m) = (self.grid.len() as i64, self.grid[0].len() as i64);
+        let mut res = String::new();
+        for i in (-self.radius * n)..((self.radius + 1) * n) {
+            for j in (-self.radius * m)..((self.radius + 1) * m) {
+                let c = self.grid[i.rem_euclid(n) as usize][j.rem_euclid(m) as usize];
+                res.push(match (c, self.distance((i, j))) {
+                    ('#', _) => '#',
+                    (_, Some(d)) if d % 2 == 1 => 'O',
+                    (_, Some(_)) => 'E',
+                    (_, None) => '.',
+                });
+            }
+            res.push('\n');
+        }
+        res
+    }
 }
 
 fn main() {
@@ -137,13 +290,29 @@ fn main() {
 
     let input = fs::read_to_string(file_path).expect("Should have been able to read the file");
 
+    // Show the distance parities up to `radius` tiles around the start with `day21 map <radius>`.
+    let args = env::args().skip(1).collect_vec();
+    if let [command, radius] = &args[..] {
+        assert_eq!(command, "map", "Usage: day21 [map <radius>]");
+        let radius = radius.parse().expect("Should be a radius");
+        let grid = parse_grid(&input);
+        let map = DistanceMap::new(&grid, radius);
+        print!("{}", map.render());
+        for tile in (-radius..=radius).cartesian_product(-radius..=radius) {
+            println!("{:?}: {:?}", tile, map.tile_stats(tile));
+        }
+        return;
+    }
+
     println!("{}", part1(&input, None));
     println!("{}", part2(&input, None));
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use aoc_2023::parse_grid;
+
+    use super::{part1, part2, part2_small, reachable_infinite, DistanceMap, TileStats};
 
     #[test]
     fn test_part1() {
@@ -185,4 +354,90 @@ mod tests {
         assert_eq!(part2(input, Some(1000)), 668697);
         assert_eq!(part2(input, Some(5000)), 16733044);
     }
+
+    #[test]
+    fn test_reachable_infinite() {
+        let input = "...........
+.....###.#.
+.###.##..#.
+..#.#...#..
+....#.#....
+.##..S####.
+.##..#...#.
+.......##..
+.##.#.####.
+.##..##.##.
+...........";
+        let grid = parse_grid(input);
+
+        for steps in [7, 64, 131, 250, 777, 1234] {
+            assert_eq!(
+                reachable_infinite(&grid, steps),
+                Ok(part2_small(input, steps))
+            );
+        }
+    }
+
+    #[test]
+    fn test_reachable_infinite_walled() {
+        let input = "#####
+#.S.#
+#####";
+        let grid = parse_grid(input);
+
+        assert_eq!(reachable_infinite(&grid, 3), Ok(2));
+        assert_eq!(reachable_infinite(&grid, 1000), Ok(1));
+        assert!(reachable_infinite(&grid, -1).is_err());
+    }
+
+    #[test]
+    fn test_distance_map() {
+        let input = "...........
+.....###.#.
+.###.##..#.
+..#.#...#..
+....#.#....
+.##..S####.
+.##..#...#.
+.......##..
+.##.#.####.
+.##..##.##.
+...........";
+        let grid = parse_grid(input);
+
+        let map = DistanceMap::new(&grid, 0);
+        assert_eq!(map.distance((5, 5)), Some(0));
+        assert_eq!(map.distance((5, 4)), Some(1));
+        assert_eq!(map.distance((5, 6)), None);
+        let stats = map.tile_stats((0, 0));
+        assert_eq!(stats.odd + stats.even, 81);
+        assert_eq!(
+            map.render().lines().take(3).collect::<Vec<_>>(),
+            vec!["EOEOEOEOEOE", "OEOEO###O#O", "E###E##OE#E"]
+        );
+
+        let map = DistanceMap::new(&grid, 1);
+        assert_eq!(map.render().lines().count(), 33);
+        assert_eq!(map.distance((5, 16)), Some(15));
+        assert_eq!(map.tile_stats((1, 1)).max_distance, Some(36));
+        assert_eq!(map.tile_stats((-1, -1)).max_distance, Some(32));
+        // An odd tile size swaps the parities of the next tile.
+        let stats = map.tile_stats((1, 0));
+        assert_eq!((stats.odd_corners, stats.even_corners), (29, 18));
+        assert_eq!(
+            DistanceMap::new(&grid, 0).tile_stats((0, 0)),
+            TileStats {
+                odd: 39,
+                even: 42,
+                max_distance: Some(14),
+                odd_corners: 18,
+                even_corners: 29,
+            }
+        );
+
+        // The diamond follows the tile's width and height.
+        let grid = parse_grid(".....\n..S..\n.....");
+        let stats = DistanceMap::new(&grid, 0).tile_stats((0, 0));
+        assert_eq!((stats.odd_corners, stats.even_corners), (4, 4));
+    }
 }
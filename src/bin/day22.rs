@@ -1,4 +1,4 @@
-use std::{collections::HashSet, fs, ops::RangeInclusive};
+use std::{collections::HashMap, env, fs, ops::RangeInclusive};
 
 use itertools::Itertools;
 
@@ -10,94 +10,12 @@ struct Block {
 }
 
 fn part1(input: &str) -> i64 {
-    let mut blocks = input.lines().map(parse_block).collect_vec();
+    let blocks = input.lines().map(parse_block).collect_vec();
+    let stack = Stack::from(blocks);
 
-    blocks.sort_by_key(|b| *b.zs.start());
-    let mut cubes = HashSet::new();
-    for block in &blocks {
-        for x in block.xs.clone() {
-            for y in block.ys.clone() {
-                for z in block.zs.clone() {
-                    cubes.insert((x, y, z));
-                }
-            }
-        }
-    }
-
-    let mut to_move = (0..blocks.len()).collect_vec();
-    while !to_move.is_empty() {
-        let mut next_to_move = vec![];
-        for i in to_move {
-            let mut stuck = false;
-            let block = &blocks[i];
-            if *block.zs.start() == 1 {
-                continue;
-            }
-            'outer: for x in block.xs.clone() {
-                for y in block.ys.clone() {
-                    let z = *block.zs.start();
-                    if cubes.contains(&(x, y, z - 1)) {
-                        stuck = true;
-                        break 'outer;
-                    }
-                }
-            }
-            if !stuck {
-                next_to_move.push(i);
-                for x in block.xs.clone() {
-                    for y in block.ys.clone() {
-                        for z in block.zs.clone() {
-                            cubes.remove(&(x, y, z));
-                            cubes.insert((x, y, z - 1));
-                        }
-                    }
-                }
-                blocks[i].zs = (block.zs.start() - 1)..=(block.zs.end() - 1);
-            }
-        }
-        to_move = next_to_move;
-    }
-
-    let mut res = blocks.len() as i64;
-    for (i, block) in blocks.iter().enumerate() {
-        for x in block.xs.clone() {
-            for y in block.ys.clone() {
-                for z in block.zs.clone() {
-                    cubes.remove(&(x, y, z));
-                }
-            }
-        }
-        for (j, block2) in blocks.iter().enumerate() {
-            if i == j {
-                continue;
-            }
-            if *block2.zs.start() == 1 {
-                continue;
-            }
-            let mut stuck = false;
-            'outer: for x in block2.xs.clone() {
-                for y in block2.ys.clone() {
-                    let z = *block2.zs.start();
-                    if cubes.contains(&(x, y, z - 1)) {
-                        stuck = true;
-                        break 'outer;
-                    }
-                }
-            }
-            if !stuck {
-                res -= 1;
-                break;
-            }
-        }
-        for x in block.xs.clone() {
-            for y in block.ys.clone() {
-                for z in block.zs.clone() {
-                    cubes.insert((x, y, z));
-                }
-            }
-        }
-    }
-    res
+    (0..stack.blocks.len())
+        .filter(|&i| stack.is_safe(i))
+        .count() as i64
 }
 
 fn parse_block(line: &str) -> Block {
@@ -111,113 +29,149 @@ fn parse_block(line: &str) -> Block {
     }
 }
 
-fn part2(input: &str) -> i64 {
-    let mut blocks = input.lines().map(parse_block).collect_vec();
+/// Settled bricks, in falling order, with their support relations.
+struct Stack {
+    blocks: Vec<Block>,
+    /// Bricks right below each brick, touching it.
+    supported_by: Vec<Vec<usize>>,
+    /// Bricks right above each brick, touching it.
+    supports: Vec<Vec<usize>>,
+    /// Immediate dominator of each brick in the support graph, None for the ground.
+    /// Removing a brick makes exactly its descendants in this tree fall.
+    dominator: Vec<Option<usize>>,
+    /// Children in the dominator tree.
+    dominated: Vec<Vec<usize>>,
+}
 
-    blocks.sort_by_key(|b| *b.zs.start());
-    let mut cubes = HashSet::new();
-    for block in &blocks {
-        for x in block.xs.clone() {
-            for y in block.ys.clone() {
-                for z in block.zs.clone() {
-                    cubes.insert((x, y, z));
-                }
+impl Stack {
+    fn from(blocks: Vec<Block>) -> Stack {
+        let (blocks, supported_by) = settle(blocks);
+
+        let mut supports = vec![vec![]; blocks.len()];
+        for (i, below) in supported_by.iter().enumerate() {
+            for &j in below {
+                supports[j].push(i);
             }
         }
-    }
 
-    let mut to_move = (0..blocks.len()).collect_vec();
-    while !to_move.is_empty() {
-        let mut next_to_move = vec![];
-        for i in to_move {
-            let mut stuck = false;
-            let block = &blocks[i];
-            if *block.zs.start() == 1 {
-                continue;
-            }
-            'outer: for x in block.xs.clone() {
-                for y in block.ys.clone() {
-                    let z = *block.zs.start();
-                    if cubes.contains(&(x, y, z - 1)) {
-                        stuck = true;
-                        break 'outer;
-                    }
-                }
-            }
-            if !stuck {
-                next_to_move.push(i);
-                for x in block.xs.clone() {
-                    for y in block.ys.clone() {
-                        for z in block.zs.clone() {
-                            cubes.remove(&(x, y, z));
-                            cubes.insert((x, y, z - 1));
-                        }
-                    }
-                }
-                blocks[i].zs = (block.zs.start() - 1)..=(block.zs.end() - 1);
+        // Supporters come first, so their dominators are known.
+        let mut dominator: Vec<Option<usize>> = vec![];
+        let mut depth: Vec<usize> = vec![];
+        for below in &supported_by {
+            let idom = below
+                .iter()
+                .map(|&j| Some(j))
+                .reduce(|a, b| lca(&dominator, &depth, a, b))
+                .flatten();
+            depth.push(idom.map_or(0, |d| depth[d] + 1));
+            dominator.push(idom);
+        }
+        let mut dominated = vec![vec![]; blocks.len()];
+        for (i, d) in dominator.iter().enumerate() {
+            if let Some(d) = d {
+                dominated[*d].push(i);
             }
         }
-        to_move = next_to_move;
+
+        Stack {
+            blocks,
+            supported_by,
+            supports,
+            dominator,
+            dominated,
+        }
+    }
+
+    /// Whether removing the brick makes no other brick fall.
+    fn is_safe(&self, brick: usize) -> bool {
+        self.supports[brick]
+            .iter()
+            .all(|&above| self.supported_by[above].len() > 1)
+    }
+
+    /// Bricks that fall, directly or not, when the brick is removed.
+    fn falling(&self, brick: usize) -> Vec<usize> {
+        let mut res = vec![];
+        let mut to_visit = self.dominated[brick].clone();
+        while let Some(i) = to_visit.pop() {
+            res.push(i);
+            to_visit.extend(&self.dominated[i]);
+        }
+        res.sort();
+        res
     }
 
-    let mut res = 0;
-    for (i, _) in blocks.iter().enumerate() {
-        res += count_moving(&[&blocks[..i], &blocks[i + 1..]].concat());
+    /// Bricks whose removal makes the brick fall.
+    fn depends_on(&self, brick: usize) -> Vec<usize> {
+        let mut res = vec![];
+        let mut cur = self.dominator[brick];
+        while let Some(d) = cur {
+            res.push(d);
+            cur = self.dominator[d];
+        }
+        res
     }
-    res
 }
 
-fn count_moving(blocks: &[Block]) -> i64 {
-    let mut blocks = blocks.to_owned();
-    blocks.sort_by_key(|b| *b.zs.start());
-    let mut cubes = HashSet::new();
-    for block in &blocks {
-        for x in block.xs.clone() {
-            for y in block.ys.clone() {
-                for z in block.zs.clone() {
-                    cubes.insert((x, y, z));
-                }
-            }
+/// Lowest common ancestor in the dominator tree, None being the ground.
+fn lca(
+    dominator: &[Option<usize>],
+    depth: &[usize],
+    mut a: Option<usize>,
+    mut b: Option<usize>,
+) -> Option<usize> {
+    while a != b {
+        match (a, b) {
+            (Some(i), Some(j)) if depth[i] >= depth[j] => a = dominator[i],
+            (Some(_), Some(j)) => b = dominator[j],
+            _ => return None,
         }
     }
+    a
+}
 
-    let mut moved = HashSet::new();
-    let mut to_move = (0..blocks.len()).collect_vec();
-    while !to_move.is_empty() {
-        let mut next_to_move = vec![];
-        for i in to_move {
-            let mut stuck = false;
-            let block = &blocks[i];
-            if *block.zs.start() == 1 {
-                continue;
-            }
-            'outer: for x in block.xs.clone() {
-                for y in block.ys.clone() {
-                    let z = *block.zs.start();
-                    if cubes.contains(&(x, y, z - 1)) {
-                        stuck = true;
-                        break 'outer;
-                    }
-                }
-            }
-            if !stuck {
-                moved.insert(i);
-                next_to_move.push(i);
-                for x in block.xs.clone() {
-                    for y in block.ys.clone() {
-                        for z in block.zs.clone() {
-                            cubes.remove(&(x, y, z));
-                            cubes.insert((x, y, z - 1));
-                        }
-                    }
-                }
-                blocks[i].zs = (block.zs.start() - 1)..=(block.zs.end() - 1);
-            }
+/// Let the bricks fall in order of bottom z, tracking the top z and owner of every column.
+/// Returns the settled bricks, in that order, with the bricks right below each brick.
+fn settle(mut blocks: Vec<Block>) -> (Vec<Block>, Vec<Vec<usize>>) {
+    blocks.sort_by_key(|b| *b.zs.start());
+    let mut heights: HashMap<(i64, i64), (i64, usize)> = HashMap::new();
+    let mut supported_by = vec![];
+    for (i, block) in blocks.iter_mut().enumerate() {
+        let columns = block
+            .xs
+            .clone()
+            .cartesian_product(block.ys.clone())
+            .collect_vec();
+        let below = columns
+            .iter()
+            .filter_map(|column| heights.get(column))
+            .collect_vec();
+        let top = below.iter().map(|(z, _)| *z).max().unwrap_or(0);
+        supported_by.push(
+            below
+                .iter()
+                .filter(|(z, _)| *z == top)
+                .map(|(_, j)| *j)
+                .unique()
+                .collect_vec(),
+        );
+
+        let height = block.zs.end() - block.zs.start();
+        block.zs = (top + 1)..=(top + 1 + height);
+        for column in columns {
+            heights.insert(column, (*block.zs.end(), i));
         }
-        to_move = next_to_move;
     }
+    (blocks, supported_by)
+}
 
-    moved.len() as i64
+fn part2(input: &str) -> i64 {
+    let blocks = input.lines().map(parse_block).collect_vec();
+    let stack = Stack::from(blocks);
+
+    (0..stack.blocks.len())
+        .map(|i| stack.falling(i).len())
+        .sum::<usize>() as i64
 }
 
 fn main() {
@@ -225,13 +179,27 @@ fn main() {
 
     let input = fs::read_to_string(file_path).expect("Should have been able to read the file");
 
+    // Show what a brick (by index in falling order) holds up and rests on with `day22 brick <i>`.
+    let args = env::args().skip(1).collect_vec();
+    if let [command, brick] = &args[..] {
+        assert_eq!(command, "brick", "Usage: day22 [brick <index>]");
+        let brick = brick.parse().expect("Should be a brick index");
+        let stack = Stack::from(input.lines().map(parse_block).collect_vec());
+        println!("{:?}", stack.blocks[brick]);
+        println!("Falls without it: {:?}", stack.falling(brick));
+        println!("Falls without any of: {:?}", stack.depends_on(brick));
+        return;
+    }
+
     println!("{}", part1(&input));
     println!("{}", part2(&input));
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use itertools::Itertools;
+
+    use super::{parse_block, part1, part2, settle, Stack};
 
     #[test]
     fn test_part1() {
@@ -258,4 +226,43 @@ mod tests {
 
         assert_eq!(part2(input), 7);
     }
+
+    #[test]
+    fn test_stack() {
+        let input = "1,0,1~1,2,1
+0,0,2~2,0,2
+0,2,3~2,2,3
+0,0,4~0,2,4
+2,0,5~2,2,5
+0,1,6~2,1,6
+1,1,8~1,1,9";
+        let stack = Stack::from(input.lines().map(parse_block).collect_vec());
+
+        // A, B, C, D, E, F, G from the puzzle.
+        assert_eq!(stack.supported_by[1], vec![0]);
+        assert_eq!(stack.supports[0], vec![1, 2]);
+        assert_eq!(stack.supported_by[5], vec![3, 4]);
+        assert_eq!(
+            (0..7).filter(|&i| stack.is_safe(i)).collect_vec(),
+            vec![1, 2, 3, 4, 6]
+        );
+        assert_eq!(stack.falling(0), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(stack.falling(5), vec![6]);
+        assert_eq!(stack.falling(3), vec![]);
+        assert_eq!(stack.depends_on(6), vec![5, 0]);
+        assert_eq!(stack.depends_on(3), vec![0]);
+    }
+
+    #[test]
+    fn test_settle() {
+        let input = "0,0,1000000~0,0,1999999
+0,0,5000000~100000,0,5000000
+100000,0,3~100000,0,3";
+        let (blocks, supported_by) = settle(input.lines().map(parse_block).collect_vec());
+
+        assert_eq!(blocks[0].zs, 1..=1);
+        assert_eq!(blocks[1].zs, 1..=1000000);
+        assert_eq!(blocks[2].zs, 1000001..=1000001);
+        assert_eq!(supported_by, vec![vec![], vec![], vec![1]]);
+    }
 }
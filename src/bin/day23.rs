@@ -1,7 +1,7 @@
 use core::panic;
 use std::{
     collections::{HashMap, HashSet},
-    fs,
+    fs, thread,
 };
 
 use aoc_2023::{grid_down, grid_left, grid_right, grid_up, parse_grid};
@@ -17,7 +17,7 @@ fn part1(input: &str) -> i64 {
 }
 
 fn backtrack(
-    grid: &Vec<Vec<char>>,
+    grid: &[Vec<char>],
     start: (usize, usize),
     end: (usize, usize),
     seen: &mut HashSet<(usize, usize)>,
@@ -46,7 +46,7 @@ fn backtrack(
     res
 }
 
-fn get_neighbors(grid: &Vec<Vec<char>>, position: (usize, usize)) -> Vec<((usize, usize), i64)> {
+fn get_neighbors(grid: &[Vec<char>], position: (usize, usize)) -> Vec<((usize, usize), i64)> {
     let mut res = vec![];
     for (neighbor, c) in [
         grid_down(grid, position),
@@ -71,94 +71,178 @@ fn get_neighbors(grid: &Vec<Vec<char>>, position: (usize, usize)) -> Vec<((usize
     res
 }
 
-fn get_next(
-    grid: &Vec<Vec<char>>,
-    prev: (usize, usize),
-    position: (usize, usize),
-) -> Option<(usize, usize)> {
-    let neighbors = get_neighbors(grid, position);
-    if neighbors.len() != 2 {
-        return None;
-    }
-    if neighbors[0].0 == prev {
-        Some(neighbors[1].0)
-    } else {
-        Some(neighbors[0].0)
-    }
+/// Maze compressed to its junctions, with the corridors between them as weighted edges.
+struct JunctionGraph {
+    junctions: Vec<(usize, usize)>,
+    /// For each junction: (junction index, corridor length).
+    edges: Vec<Vec<(usize, i64)>>,
+    start: usize,
+    goal: usize,
 }
 
-fn get_far_neighbor(
-    grid: &Vec<Vec<char>>,
-    position: (usize, usize),
-    direction: (usize, usize),
-) -> ((usize, usize), i64) {
-    let mut prev = position;
-    let mut cur = direction;
-    let mut len = 1;
-    while cur != position {
-        let maybe_next = get_next(grid, prev, cur);
-        if let Some(next) = maybe_next {
-            prev = cur;
-            cur = next;
-            len += 1;
-        } else {
-            return (cur, len);
+impl JunctionGraph {
+    /// Build the graph of a maze without slopes.
+    fn from(grid: &[Vec<char>], start: (usize, usize), goal: (usize, usize)) -> JunctionGraph {
+        let is_junction =
+            |p: (usize, usize)| p == start || p == goal || get_neighbors(grid, p).len() > 2;
+        let mut indices = HashMap::from([(start, 0)]);
+        let mut junctions = vec![start];
+        let mut edges = vec![];
+        let mut i = 0;
+        while i < junctions.len() {
+            let junction = junctions[i];
+            let mut junction_edges = vec![];
+            for (neighbor, _) in get_neighbors(grid, junction) {
+                // Walk the corridor up to the next junction, skipping dead ends.
+                let mut prev = junction;
+                let mut cur = neighbor;
+                let mut len = 1;
+                while !is_junction(cur) {
+                    let next = get_neighbors(grid, cur)
+                        .into_iter()
+                        .map(|(n, _)| n)
+                        .find(|&n| n != prev);
+                    match next {
+                        Some(next) => {
+                            prev = cur;
+                            cur = next;
+                            len += 1;
+                        }
+                        None => break,
+                    }
+                }
+                if !is_junction(cur) {
+                    continue;
+                }
+                let index = *indices.entry(cur).or_insert_with(|| {
+                    junctions.push(cur);
+                    junctions.len() - 1
+                });
+                junction_edges.push((index, len));
+            }
+            edges.push(junction_edges);
+            i += 1;
         }
-    }
-    (position, 0)
-}
+        assert!(junctions.len() <= 64, "Should fit in a u64 mask");
+        let goal = *indices.get(&goal).expect("Should reach the goal");
 
-fn get_far_neighbors(
-    grid: &Vec<Vec<char>>,
-    position: (usize, usize),
-) -> Vec<((usize, usize), i64)> {
-    let mut res = vec![];
-    for neighbor in get_neighbors(grid, position) {
-        let far_neighbor = get_far_neighbor(grid, position, neighbor.0);
-        if far_neighbor.0 != position {
-            res.push(far_neighbor);
+        JunctionGraph {
+            junctions,
+            edges,
+            start: 0,
+            goal,
         }
     }
-    res
-}
 
-type Neighbors = Vec<((usize, usize), i64)>;
+    /// Longest simple path from start to goal, as (length, junction positions).
+    /// The search is split on the paths of `split_depth` first edges, shared by `threads` threads.
+    fn longest_path(
+        &self,
+        split_depth: usize,
+        threads: usize,
+    ) -> Option<(i64, Vec<(usize, usize)>)> {
+        let mut prefixes = vec![vec![self.start]];
+        for _ in 0..split_depth {
+            prefixes = prefixes
+                .into_iter()
+                .flat_map(|prefix| {
+                    let last = *prefix.last().expect("Should not be empty");
+                    if last == self.goal {
+                        return vec![prefix];
+                    }
+                    self.edges[last]
+                        .iter()
+                        .filter(|(n, _)| !prefix.contains(n))
+                        .map(|&(n, _)| {
+                            let mut prefix = prefix.clone();
+                            prefix.push(n);
+                            prefix
+                        })
+                        .collect()
+                })
+                .collect();
+        }
 
-fn backtrack2(
-    grid: &Vec<Vec<char>>,
-    start: (usize, usize),
-    end: (usize, usize),
-    seen: &mut HashSet<(usize, usize)>,
-    cache: &mut HashMap<(usize, usize), Neighbors>,
-) -> Option<i64> {
-    if start == end {
-        return Some(0);
-    }
-    let neighbors = if let Some(neighbors) = cache.get(&start) {
-        neighbors.clone()
-    } else {
-        let neighbors = get_far_neighbors(grid, start);
-        cache.insert(start, neighbors.clone());
-        neighbors
-    };
-    if neighbors.is_empty() {
-        return None;
+        let chunk_size = prefixes.len().div_ceil(threads.max(1));
+        thread::scope(|scope| {
+            let handles = prefixes
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .filter_map(|prefix| self.longest_path_from(prefix))
+                            .max_by_key(|(length, _)| *length)
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .filter_map(|h| h.join().expect("Should not panic"))
+                .max_by_key(|(length, _)| *length)
+                .map(|(length, path)| (length, path.iter().map(|&n| self.junctions[n]).collect()))
+        })
     }
-    let mut res = None;
-    for (neighbor, dd) in neighbors {
-        if !seen.contains(&neighbor) {
-            seen.insert(neighbor);
-            let d = backtrack2(grid, neighbor, end, seen, cache);
-            if let Some(d) = d {
-                res = match res {
-                    None => Some(d + dd),
-                    Some(res) => Some(res.max(d + dd)),
-                };
+
+    /// Iterative DFS over the paths extending `prefix`.
+    fn longest_path_from(&self, prefix: &[usize]) -> Option<(i64, Vec<usize>)> {
+        // Once there, the junction next to the goal has to go to the goal.
+        let before_goal = match self.edges[self.goal][..] {
+            [(n, _)] => Some(n),
+            _ => None,
+        };
+
+        let mut path = prefix.to_vec();
+        let mut lengths = vec![0];
+        for w in prefix.windows(2) {
+            let d = self.edge_length(w[0], w[1]);
+            lengths.push(lengths.last().expect("Should not be empty") + d);
+        }
+        let mut mask = prefix.iter().fold(0u64, |mask, &n| mask | (1 << n));
+        let mut next_edge = vec![0; path.len()];
+        let mut best: Option<(i64, Vec<usize>)> = None;
+
+        loop {
+            let node = *path.last().expect("Should not be empty");
+            let length = *lengths.last().expect("Should not be empty");
+            let depth = path.len() - 1;
+            let mut exhausted = node == self.goal;
+            if exhausted {
+                if best.as_ref().is_none_or(|(best, _)| length > *best) {
+                    best = Some((length, path.clone()));
+                }
+            } else if let Some(&(n, d)) = self.edges[node].get(next_edge[depth]) {
+                next_edge[depth] += 1;
+                if mask & (1 << n) == 0 && (Some(node) != before_goal || n == self.goal) {
+                    path.push(n);
+                    lengths.push(length + d);
+                    next_edge.push(0);
+                    mask |= 1 << n;
+                }
+            } else {
+                exhausted = true;
+            }
+
+            if exhausted {
+                if path.len() == prefix.len() {
+                    break;
+                }
+                path.pop();
+                lengths.pop();
+                next_edge.pop();
+                mask &= !(1 << node);
             }
-            seen.remove(&neighbor);
         }
+        best
+    }
+
+    fn edge_length(&self, from: usize, to: usize) -> i64 {
+        self.edges[from]
+            .iter()
+            .find(|(n, _)| *n == to)
+            .expect("Should be an edge")
+            .1
     }
-    res
 }
 
 fn part2(input: &str) -> i64 {
@@ -166,10 +250,9 @@ fn part2(input: &str) -> i64 {
 
     let start = (0usize, 1usize);
     let goal = (grid.len() - 1, grid[0].len() - 2);
-    let mut seen = HashSet::new();
-    let mut cache = HashMap::new();
+    let graph = JunctionGraph::from(&grid, start, goal);
 
-    backtrack2(&grid, start, goal, &mut seen, &mut cache).expect("Should be a valid path")
+    graph.longest_path(3, 4).expect("Should be a valid path").0
 }
 
 fn main() {
@@ -183,7 +266,9 @@ fn main() {
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use aoc_2023::parse_grid;
+
+    use super::{part1, part2, JunctionGraph};
 
     #[test]
     fn test_part1() {
@@ -242,4 +327,52 @@ mod tests {
 
         assert_eq!(part2(input), 154);
     }
+
+    #[test]
+    fn test_longest_path() {
+        let input = "#.#####################
+#.......#########...###
+#######.#########.#.###
+###.....#.>.>.###.#.###
+###v#####.#v#.###.#.###
+###.>...#.#.#.....#...#
+###v###.#.#.#########.#
+###...#.#.#.......#...#
+#####.#.#.#######.#.###
+#.....#.#.#.......#...#
+#.#####.#.#.#########v#
+#.#...#...#...###...>.#
+#.#.#v#######v###.###v#
+#...#.>.#...>.>.#.###.#
+#####v#.#.###v#.#.###.#
+#.....#...#...#.#.#...#
+#.#########.###.#.#.###
+#...###...#...#...#.###
+###.###.#.###v#####v###
+#...#...#.#.>.>.#.>.###
+#.###.###.#.###.#.#v###
+#.....###...###...#...#
+#####################.#";
+        let grid = parse_grid(&input.replace(['>', '<', '^', 'v'], "."));
+        let graph = JunctionGraph::from(&grid, (0, 1), (22, 21));
+
+        assert_eq!(graph.junctions.len(), 9);
+        let (length, path) = graph.longest_path(0, 1).expect("Should be a valid path");
+        assert_eq!(length, 154);
+        assert_eq!(path[0], (0, 1));
+        assert_eq!(*path.last().unwrap(), (22, 21));
+        let path = path
+            .iter()
+            .map(|p| graph.junctions.iter().position(|j| j == p).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            path.windows(2)
+                .map(|w| graph.edge_length(w[0], w[1]))
+                .sum::<i64>(),
+            154
+        );
+        for threads in [1, 3, 64] {
+            assert_eq!(graph.longest_path(2, threads).map(|(l, _)| l), Some(154));
+        }
+    }
 }
@@ -2,6 +2,8 @@ use std::fs;
 
 use aoc_2023::split_parse;
 use itertools::Itertools;
+use num::{BigRational, Signed, Zero};
+#[cfg(feature = "z3")]
 use z3::ast::{self, Ast};
 
 #[derive(Clone, Copy, Debug)]
@@ -33,42 +35,169 @@ fn part1(input: &str, bounds: Option<(i64, i64)>) -> i64 {
     let bounds = bounds.unwrap_or((200000000000000, 400000000000000));
     let hails = input.lines().map(Hail::from).collect::<Vec<_>>();
 
+    crossings(&hails, bounds).len() as i64
+}
+
+/// Where two hail paths meet in the xy plane, in the future of both hails.
+#[derive(Clone, Debug, PartialEq)]
+enum Crossing {
+    /// Single point, reached at t1 by the first hail and t2 by the second.
+    Point {
+        x: BigRational,
+        y: BigRational,
+        t1: BigRational,
+        t2: BigRational,
+    },
+    /// Same line, overlapping for the first hail's t in [start, end] (no end if unbounded).
+    Overlap {
+        start: BigRational,
+        end: Option<BigRational>,
+    },
+}
+
+/// List the pairs of hails (by index) whose paths cross inside the test area.
+fn crossings(hails: &[Hail], bounds: (i64, i64)) -> Vec<(usize, usize, Crossing)> {
     hails
         .iter()
-        .combinations(2)
-        .filter(|c| intersect(*c[0], *c[1], bounds))
-        .count() as i64
+        .enumerate()
+        .tuple_combinations()
+        .filter_map(|((i1, h1), (i2, h2))| {
+            let crossing = intersect(h1, h2)?;
+            crossing_inside(h1, &crossing, bounds).then_some((i1, i2, crossing))
+        })
+        .collect()
 }
 
-fn intersect(h1: Hail, h2: Hail, bounds: (i64, i64)) -> bool {
-    // Exact check for parallels.
-    if h1.vx * h2.vy == h1.vy * h2.vx {
-        return false;
+fn intersect(h1: &Hail, h2: &Hail) -> Option<Crossing> {
+    // A still hail crosses a path at time 0, if it sits on the other hail's ray.
+    match ((h1.vx, h1.vy) == (0, 0), (h2.vx, h2.vy) == (0, 0)) {
+        (true, true) => {
+            return ((h1.x, h1.y) == (h2.x, h2.y)).then(|| Crossing::Point {
+                x: rational(h1.x.into()),
+                y: rational(h1.y.into()),
+                t1: BigRational::zero(),
+                t2: BigRational::zero(),
+            })
+        }
+        (false, true) => {
+            return time_on_ray(h1, h2).map(|t1| Crossing::Point {
+                x: rational(h2.x.into()),
+                y: rational(h2.y.into()),
+                t1,
+                t2: BigRational::zero(),
+            })
+        }
+        (true, false) => {
+            return time_on_ray(h2, h1).map(|t2| Crossing::Point {
+                x: rational(h1.x.into()),
+                y: rational(h1.y.into()),
+                t1: BigRational::zero(),
+                t2,
+            })
+        }
+        (false, false) => {}
+    }
+    let (dx, dy) = (
+        i128::from(h2.x) - i128::from(h1.x),
+        i128::from(h2.y) - i128::from(h1.y),
+    );
+    let (v1x, v1y) = (i128::from(h1.vx), i128::from(h1.vy));
+    let (v2x, v2y) = (i128::from(h2.vx), i128::from(h2.vy));
+
+    // Solve p1 + t1 * v1 = p2 + t2 * v2 with cross products.
+    let det = v1x * v2y - v1y * v2x;
+    if det != 0 {
+        let t1 = BigRational::new((dx * v2y - dy * v2x).into(), det.into());
+        let t2 = BigRational::new((dx * v1y - dy * v1x).into(), det.into());
+        if t1.is_negative() || t2.is_negative() {
+            return None;
+        }
+        let x = rational(h1.x.into()) + &t1 * rational(v1x);
+        let y = rational(h1.y.into()) + &t1 * rational(v1y);
+        return Some(Crossing::Point { x, y, t1, t2 });
     }
-    let (a1, b1) = compute_ab(h1);
-    let (a2, b2) = compute_ab(h2);
-    let x = (b2 - b1) / (a1 - a2);
-    if (x < (h1.x as f64) && h1.vx > 0) || (x > (h1.x as f64) && h1.vx < 0) {
-        return false;
+
+    // Parallel, but on different lines.
+    if dx * v1y - dy * v1x != 0 {
+        return None;
     }
-    if (x < (h2.x as f64) && h2.vx > 0) || (x > (h2.x as f64) && h2.vx < 0) {
-        return false;
+
+    // Collinear: s is the first hail's t when it is at the second hail's start.
+    let s = BigRational::new((dx * v1x + dy * v1y).into(), (v1x * v1x + v1y * v1y).into());
+    if v1x * v2x + v1y * v2y > 0 {
+        Some(Crossing::Overlap {
+            start: s.max(BigRational::zero()),
+            end: None,
+        })
+    } else if s.is_negative() {
+        // Moving apart.
+        None
+    } else {
+        Some(Crossing::Overlap {
+            start: BigRational::zero(),
+            end: Some(s),
+        })
     }
-    let y = a1 * x + b1;
-    let min = bounds.0 as f64;
-    let max = bounds.1 as f64;
+}
 
-    min <= x && x <= max && min <= y && y <= max
+/// When the moving hail goes through the still hail's position, if it ever does.
+fn time_on_ray(moving: &Hail, still: &Hail) -> Option<BigRational> {
+    let (dx, dy) = (
+        i128::from(still.x) - i128::from(moving.x),
+        i128::from(still.y) - i128::from(moving.y),
+    );
+    let (vx, vy) = (i128::from(moving.vx), i128::from(moving.vy));
+    let dot = dx * vx + dy * vy;
+    (dx * vy - dy * vx == 0 && dot >= 0)
+        .then(|| BigRational::new(dot.into(), (vx * vx + vy * vy).into()))
 }
 
-fn compute_ab(h: Hail) -> (f64, f64) {
-    // TODO: check for 0s
-    let a = (h.vy as f64) / (h.vx as f64);
-    let b = (h.y as f64) - a * (h.x as f64);
-    (a, b)
+fn crossing_inside(h1: &Hail, crossing: &Crossing, bounds: (i64, i64)) -> bool {
+    let (min, max) = (rational(bounds.0.into()), rational(bounds.1.into()));
+    match crossing {
+        Crossing::Point { x, y, .. } => &min <= x && x <= &max && &min <= y && y <= &max,
+        Crossing::Overlap { start, end } => {
+            // Intersect the overlapping times with the times spent inside the area on each axis.
+            let mut start = start.clone();
+            let mut end = end.clone();
+            for (p, v) in [(h1.x, h1.vx), (h1.y, h1.vy)] {
+                let (p, v) = (rational(p.into()), rational(v.into()));
+                if v.is_zero() {
+                    if p < min || p > max {
+                        return false;
+                    }
+                    continue;
+                }
+                let (t_min, t_max) = ((&min - &p) / &v, (&max - &p) / &v);
+                let (t_min, t_max) = if v.is_negative() {
+                    (t_max, t_min)
+                } else {
+                    (t_min, t_max)
+                };
+                start = start.max(t_min);
+                end = Some(end.map_or(t_max.clone(), |end| end.min(t_max)));
+            }
+            end.is_none_or(|end| start <= end)
+        }
+    }
+}
+
+fn rational(n: i128) -> BigRational {
+    BigRational::from_integer(n.into())
 }
 
+#[cfg(feature = "z3")]
 fn part2(input: &str) -> i64 {
+    part2_z3(input)
+}
+
+#[cfg(not(feature = "z3"))]
+fn part2(input: &str) -> i64 {
+    part2_linear(input)
+}
+
+#[cfg(feature = "z3")]
+fn part2_z3(input: &str) -> i64 {
     let hails = input.lines().map(Hail::from).collect::<Vec<_>>();
 
     let z3_conf = z3::Config::new();
@@ -126,6 +255,84 @@ fn part2(input: &str) -> i64 {
     x + y + z
 }
 
+// The rock (p, v) hits hail i iff (p - pi) x (v - vi) = 0.
+// The p x v term is shared by all hails, so subtracting the equations of two hails
+// gives 3 linear equations: p x (vj - vi) + (pj - pi) x v = pj x vj - pi x vi.
+// Two pairs of hails give a 6x6 system, solved exactly.
+#[cfg_attr(feature = "z3", allow(dead_code))]
+fn part2_linear(input: &str) -> i64 {
+    let hails = input.lines().map(Hail::from).collect::<Vec<_>>();
+
+    let rock = hails
+        .iter()
+        .tuple_combinations()
+        .find_map(|(h0, h1, h2)| {
+            let mut system = linear_equations(h0, h1);
+            system.extend(linear_equations(h0, h2));
+            solve_linear(system)
+        })
+        .expect("Should have a solution");
+
+    let sum = &rock[0] + &rock[1] + &rock[2];
+    assert!(sum.is_integer(), "Should be an integer position");
+    i64::try_from(sum.to_integer()).expect("Should be an i64")
+}
+
+fn linear_equations(hi: &Hail, hj: &Hail) -> Vec<Vec<BigRational>> {
+    let pi = [hi.x, hi.y, hi.z].map(i128::from);
+    let vi = [hi.vx, hi.vy, hi.vz].map(i128::from);
+    let pj = [hj.x, hj.y, hj.z].map(i128::from);
+    let vj = [hj.vx, hj.vy, hj.vz].map(i128::from);
+    let dp = [pj[0] - pi[0], pj[1] - pi[1], pj[2] - pi[2]];
+    let dv = [vj[0] - vi[0], vj[1] - vi[1], vj[2] - vi[2]];
+    let ci = cross(pi, vi);
+    let cj = cross(pj, vj);
+
+    // Unknowns: px, py, pz, vx, vy, vz, then the right hand side.
+    let rows = [
+        [0, dv[2], -dv[1], 0, -dp[2], dp[1], cj[0] - ci[0]],
+        [-dv[2], 0, dv[0], dp[2], 0, -dp[0], cj[1] - ci[1]],
+        [dv[1], -dv[0], 0, -dp[1], dp[0], 0, cj[2] - ci[2]],
+    ];
+    rows.iter()
+        .map(|row| row.iter().map(|&c| rational(c)).collect())
+        .collect()
+}
+
+fn cross(a: [i128; 3], b: [i128; 3]) -> [i128; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Gauss-Jordan elimination on an augmented matrix, None if singular.
+fn solve_linear(mut system: Vec<Vec<BigRational>>) -> Option<Vec<BigRational>> {
+    let n = system.len();
+    for col in 0..n {
+        let pivot = (col..n)
+            .filter(|&row| !system[row][col].is_zero())
+            .max_by_key(|&row| system[row][col].abs())?;
+        system.swap(col, pivot);
+        let pivot = system[col][col].clone();
+        for value in system[col].iter_mut() {
+            *value /= &pivot;
+        }
+        for row in 0..n {
+            if row == col || system[row][col].is_zero() {
+                continue;
+            }
+            let factor = system[row][col].clone();
+            let pivot_row = system[col].clone();
+            for (value, pivot_value) in system[row].iter_mut().zip(pivot_row).skip(col) {
+                *value -= &factor * pivot_value;
+            }
+        }
+    }
+    Some(system.into_iter().map(|row| row[n].clone()).collect())
+}
+
 fn main() {
     let file_path = "data/day24_input.txt";
 
@@ -137,7 +344,9 @@ fn main() {
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use num::BigRational;
+
+    use super::{crossings, intersect, part1, part2, part2_linear, Crossing, Hail};
 
     #[test]
     fn test_part1() {
@@ -150,6 +359,109 @@ mod tests {
         assert_eq!(part1(input, Some((7, 27))), 2);
     }
 
+    #[test]
+    fn test_crossings() {
+        let input = "19, 13, 30 @ -2,  1, -2
+18, 19, 22 @ -1, -1, -2
+20, 25, 34 @ -2, -2, -4
+12, 31, 28 @ -1, -2, -1
+20, 19, 15 @  1, -5, -3";
+        let hails = input.lines().map(Hail::from).collect::<Vec<_>>();
+
+        let found = crossings(&hails, (7, 27));
+        assert_eq!(
+            found
+                .iter()
+                .map(|(i1, i2, _)| (*i1, *i2))
+                .collect::<Vec<_>>(),
+            vec![(0, 1), (0, 2)]
+        );
+        assert_eq!(
+            found[0].2,
+            Crossing::Point {
+                x: BigRational::new(43.into(), 3.into()),
+                y: BigRational::new(46.into(), 3.into()),
+                t1: BigRational::new(7.into(), 3.into()),
+                t2: BigRational::new(11.into(), 3.into()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_intersect_vertical() {
+        let h1 = Hail::from("10, 0, 0 @ 0, 1, 0");
+        let h2 = Hail::from("0, 5, 0 @ 2, 0, 0");
+
+        assert_eq!(
+            intersect(&h1, &h2),
+            Some(Crossing::Point {
+                x: BigRational::from_integer(10.into()),
+                y: BigRational::from_integer(5.into()),
+                t1: BigRational::from_integer(5.into()),
+                t2: BigRational::from_integer(5.into()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_intersect_still() {
+        let moving = Hail::from("0, 0, 0 @ 2, 1, 0");
+        let ahead = Hail::from("6, 3, 0 @ 0, 0, 5");
+        let behind = Hail::from("-2, -1, 0 @ 0, 0, 0");
+        let aside = Hail::from("6, 4, 0 @ 0, 0, 0");
+
+        assert_eq!(
+            intersect(&moving, &ahead),
+            Some(Crossing::Point {
+                x: BigRational::from_integer(6.into()),
+                y: BigRational::from_integer(3.into()),
+                t1: BigRational::from_integer(3.into()),
+                t2: BigRational::from_integer(0.into()),
+            })
+        );
+        assert_eq!(
+            intersect(&ahead, &moving),
+            Some(Crossing::Point {
+                x: BigRational::from_integer(6.into()),
+                y: BigRational::from_integer(3.into()),
+                t1: BigRational::from_integer(0.into()),
+                t2: BigRational::from_integer(3.into()),
+            })
+        );
+        assert_eq!(intersect(&moving, &behind), None);
+        assert_eq!(intersect(&moving, &aside), None);
+        assert!(intersect(&ahead, &ahead).is_some());
+        assert_eq!(intersect(&ahead, &aside), None);
+    }
+
+    #[test]
+    fn test_intersect_collinear() {
+        let h1 = Hail::from("0, 0, 0 @ 1, 1, 0");
+        let same_way = Hail::from("4, 4, 0 @ 2, 2, 0");
+        let facing = Hail::from("4, 4, 0 @ -1, -1, 0");
+        let apart = Hail::from("-4, -4, 0 @ -1, -1, 0");
+
+        assert_eq!(
+            intersect(&h1, &same_way),
+            Some(Crossing::Overlap {
+                start: BigRational::from_integer(4.into()),
+                end: None,
+            })
+        );
+        assert_eq!(
+            intersect(&h1, &facing),
+            Some(Crossing::Overlap {
+                start: BigRational::from_integer(0.into()),
+                end: Some(BigRational::from_integer(4.into())),
+            })
+        );
+        assert_eq!(intersect(&h1, &apart), None);
+
+        let hails = [h1, facing];
+        assert_eq!(crossings(&hails, (2, 3)).len(), 1);
+        assert_eq!(crossings(&hails, (5, 6)).len(), 0);
+    }
+
     #[test]
     fn test_part2() {
         let input = "19, 13, 30 @ -2,  1, -2
@@ -160,4 +472,15 @@ mod tests {
 
         assert_eq!(part2(input), 47);
     }
+
+    #[test]
+    fn test_part2_linear() {
+        let input = "19, 13, 30 @ -2,  1, -2
+18, 19, 22 @ -1, -1, -2
+20, 25, 34 @ -2, -2, -4
+12, 31, 28 @ -1, -2, -1
+20, 19, 15 @  1, -5, -3";
+
+        assert_eq!(part2_linear(input), 47);
+    }
 }
@@ -0,0 +1,19 @@
+use std::{env, fs};
+
+use aoc_2023::nonogram::{render, Nonogram, Solution};
+
+fn main() {
+    let file_path = env::args()
+        .nth(1)
+        .expect("Usage: nonogram <file with row clues, an empty line, then column clues>");
+
+    let input = fs::read_to_string(file_path).expect("Should have been able to read the file");
+
+    match Nonogram::from(&input).solve() {
+        Solution::None => println!("No solution"),
+        Solution::Unique(picture) => println!("{}\nUnique", render(&picture)),
+        Solution::Multiple(first, second) => {
+            println!("{}\n\n{}\nNot unique", render(&first), render(&second))
+        }
+    }
+}
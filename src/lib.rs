@@ -1,5 +1,8 @@
 use std::str::FromStr;
 
+pub mod line_solver;
+pub mod nonogram;
+
 /// Split a string and return values that can be parsed.
 /// Note that the wrong type may result in silent failures.
 pub fn split_parse<T: FromStr>(s: &str) -> Vec<T> {
@@ -16,7 +19,7 @@ pub fn parse_grid(input: &str) -> Vec<Vec<char>> {
 }
 
 /// Find an element in a grid.
-pub fn grid_find<T: PartialEq>(grid: &Vec<Vec<T>>, hay: T) -> Option<(usize, usize)> {
+pub fn grid_find<T: PartialEq>(grid: &[Vec<T>], hay: T) -> Option<(usize, usize)> {
     (0..grid.len())
         .flat_map(|i| (0..grid[0].len()).map(move |j| (i, j)))
         .find(|(i, j)| grid[*i][*j] == hay)
@@ -24,7 +27,7 @@ pub fn grid_find<T: PartialEq>(grid: &Vec<Vec<T>>, hay: T) -> Option<(usize, usi
 
 /// Get the neighbors in a grid (trig order).
 pub fn grid_neighbors8<T: Copy>(
-    grid: &Vec<Vec<T>>,
+    grid: &[Vec<T>],
     start: (usize, usize),
 ) -> Vec<((usize, usize), T)> {
     let (i, j) = start;
@@ -60,7 +63,7 @@ pub fn grid_neighbors8<T: Copy>(
 
 /// Get the element below in a grid.
 pub fn grid_down<T: PartialEq + Copy>(
-    grid: &Vec<Vec<T>>,
+    grid: &[Vec<T>],
     start: (usize, usize),
 ) -> Option<((usize, usize), T)> {
     let (i, j) = start;
@@ -79,7 +82,7 @@ pub fn grid_down<T: PartialEq + Copy>(
 
 /// Get the element above in a grid.
 pub fn grid_up<T: PartialEq + Copy>(
-    grid: &Vec<Vec<T>>,
+    grid: &[Vec<T>],
     start: (usize, usize),
 ) -> Option<((usize, usize), T)> {
     let (i, j) = start;
@@ -98,7 +101,7 @@ pub fn grid_up<T: PartialEq + Copy>(
 
 /// Get the element to the left in a grid.
 pub fn grid_left<T: PartialEq + Copy>(
-    grid: &Vec<Vec<T>>,
+    grid: &[Vec<T>],
     start: (usize, usize),
 ) -> Option<((usize, usize), T)> {
     let (i, j) = start;
@@ -117,7 +120,7 @@ pub fn grid_left<T: PartialEq + Copy>(
 
 /// Get the element to the right in a grid.
 pub fn grid_right<T: PartialEq + Copy>(
-    grid: &Vec<Vec<T>>,
+    grid: &[Vec<T>],
     start: (usize, usize),
 ) -> Option<((usize, usize), T)> {
     let (i, j) = start;
@@ -0,0 +1,323 @@
+use rand::Rng;
+
+/// Arrangements of runs of filled cells (`#`) in a line, some cells being empty (`.`) or unknown (`?`).
+pub struct Line<'a> {
+    cells: &'a [u8],
+    groups: &'a [usize],
+    /// Cells once repeated, joined by unknown cells.
+    len: usize,
+    /// Groups once repeated.
+    group_count: usize,
+    /// Cells from `k` that can all be filled.
+    run: Vec<usize>,
+    /// `ways[k * (group_count + 1) + i]`: arrangements of the groups from `i` in the cells from `k`.
+    /// Saturates, as only the positive counts matter for big lines.
+    ways: Vec<i64>,
+}
+
+impl Line<'_> {
+    pub fn new<'a>(cells: &'a [u8], groups: &'a [usize]) -> Line<'a> {
+        Line::unfolded(cells, groups, 1)
+    }
+
+    /// The line repeated `unfold` times, joined by `?`, indexed in place.
+    pub fn unfolded<'a>(cells: &'a [u8], groups: &'a [usize], unfold: usize) -> Line<'a> {
+        let len = cells.len() * unfold + unfold.saturating_sub(1);
+        let group_count = groups.len() * unfold;
+        let mut line = Line {
+            cells,
+            groups,
+            len,
+            group_count,
+            run: vec![0; len + 1],
+            ways: vec![0; (len + 1) * (group_count + 1)],
+        };
+        for k in (0..len).rev() {
+            line.run[k] = if line.cell(k) == b'.' {
+                0
+            } else {
+                line.run[k + 1] + 1
+            };
+        }
+
+        let last = line.index(len, group_count);
+        line.ways[last] = 1;
+        for k in (0..len).rev() {
+            for i in 0..=group_count {
+                let mut res = 0i64;
+                if line.cell(k) != b'#' {
+                    res = res.saturating_add(line.ways[line.index(k + 1, i)]);
+                }
+                if let Some(next) = line.place(k, i) {
+                    res = res.saturating_add(line.ways[line.index(next, i + 1)]);
+                }
+                let current = line.index(k, i);
+                line.ways[current] = res;
+            }
+        }
+        line
+    }
+
+    fn cell(&self, k: usize) -> u8 {
+        let n = self.cells.len();
+        if k % (n + 1) == n {
+            b'?'
+        } else {
+            self.cells[k % (n + 1)]
+        }
+    }
+
+    fn group(&self, i: usize) -> usize {
+        self.groups[i % self.groups.len()]
+    }
+
+    fn index(&self, k: usize, i: usize) -> usize {
+        k * (self.group_count + 1) + i
+    }
+
+    /// Cell after the separator, if group `i` can start at cell `k`.
+    fn place(&self, k: usize, i: usize) -> Option<usize> {
+        if i == self.group_count || self.cell(k) == b'.' {
+            return None;
+        }
+        let end = k + self.group(i);
+        let n = self.len;
+        (self.group(i) <= self.run[k] && (end == n || self.cell(end) != b'#'))
+            .then_some((end + 1).min(n))
+    }
+
+    /// Number of arrangements, `None` if it reaches `i64::MAX`, where the counts saturate.
+    pub fn count(&self) -> Option<i64> {
+        (self.ways[0] < i64::MAX).then_some(self.ways[0])
+    }
+
+    fn is_possible(&self) -> bool {
+        self.ways[0] > 0
+    }
+
+    /// Every arrangement, as cells that are all `#` or `.`.
+    pub fn arrangements(&self) -> Arrangements<'_> {
+        Arrangements {
+            line: self,
+            stack: if self.is_possible() {
+                vec![(0, 0, self.cells())]
+            } else {
+                vec![]
+            },
+        }
+    }
+
+    /// A random arrangement, each one being as likely while `count` does not saturate.
+    pub fn sample(&self, rng: &mut impl Rng) -> Option<Vec<u8>> {
+        if !self.is_possible() {
+            return None;
+        }
+        let mut cells = self.cells();
+        let (mut k, mut i) = (0, 0);
+        while k < self.len {
+            let empty = if cells[k] != b'#' {
+                self.ways[self.index(k + 1, i)]
+            } else {
+                0
+            };
+            if rng.gen_range(0..self.ways[self.index(k, i)]) < empty {
+                cells[k] = b'.';
+                k += 1;
+            } else {
+                let next = self.place(k, i).expect("Should have a weight");
+                self.fill(&mut cells, k, i);
+                (k, i) = (next, i + 1);
+            }
+        }
+        Some(cells)
+    }
+
+    /// The cells once unfolded.
+    fn cells(&self) -> Vec<u8> {
+        (0..self.len).map(|k| self.cell(k)).collect()
+    }
+
+    /// Group `i` from cell `k`, and its separator.
+    fn fill(&self, cells: &mut [u8], k: usize, i: usize) {
+        let end = k + self.group(i);
+        cells[k..end].iter_mut().for_each(|c| *c = b'#');
+        if end < cells.len() {
+            cells[end] = b'.';
+        }
+    }
+
+    /// Positions and states of the cells that are the same in every arrangement, unknown at first.
+    pub fn forced_cells(&self) -> Vec<(usize, u8)> {
+        self.intersect()
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .filter(|&(k, c)| c != b'?' && self.cell(k) == b'?')
+            .collect()
+    }
+
+    /// Cells with the same state in every arrangement, the others as `?`, or `None` without any.
+    pub fn intersect(&self) -> Option<Vec<u8>> {
+        if !self.is_possible() {
+            return None;
+        }
+        let (n, g) = (self.len, self.group_count);
+        let mut can_fill = vec![false; n];
+        let mut can_empty = vec![false; n];
+        let mut reachable = vec![false; self.ways.len()];
+        reachable[0] = true;
+        for k in 0..n {
+            for i in 0..=g {
+                if !reachable[self.index(k, i)] {
+                    continue;
+                }
+                if self.cell(k) != b'#' && self.ways[self.index(k + 1, i)] > 0 {
+                    can_empty[k] = true;
+                    reachable[self.index(k + 1, i)] = true;
+                }
+                if let Some(next) = self.place(k, i) {
+                    if self.ways[self.index(next, i + 1)] > 0 {
+                        let end = k + self.group(i);
+                        can_fill[k..end].iter_mut().for_each(|c| *c = true);
+                        if end < n {
+                            can_empty[end] = true;
+                        }
+                        reachable[self.index(next, i + 1)] = true;
+                    }
+                }
+            }
+        }
+
+        Some(
+            can_fill
+                .iter()
+                .zip(&can_empty)
+                .map(|(&fill, &empty)| match (fill, empty) {
+                    (true, false) => b'#',
+                    (false, true) => b'.',
+                    _ => b'?',
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Depth-first walk of the arrangements, only following states with some.
+pub struct Arrangements<'a> {
+    line: &'a Line<'a>,
+    /// Next cell, next group, cells so far.
+    stack: Vec<(usize, usize, Vec<u8>)>,
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let line = self.line;
+        while let Some((k, i, mut cells)) = self.stack.pop() {
+            if k == cells.len() {
+                return Some(cells);
+            }
+            if let Some(next) = line.place(k, i) {
+                if line.ways[line.index(next, i + 1)] > 0 {
+                    let mut placed = cells.clone();
+                    line.fill(&mut placed, k, i);
+                    self.stack.push((next, i + 1, placed));
+                }
+            }
+            if cells[k] != b'#' && line.ways[line.index(k + 1, i)] > 0 {
+                cells[k] = b'.';
+                self.stack.push((k + 1, i, cells));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::Line;
+
+    #[test]
+    fn test_line() {
+        assert_eq!(Line::new(b"???.###", &[1, 1, 3]).count(), Some(1));
+        assert_eq!(Line::new(b"?###????????", &[3, 2, 1]).count(), Some(10));
+        assert_eq!(Line::new(b"#??", &[2, 1]).count(), Some(0));
+        assert_eq!(Line::new(b"", &[]).count(), Some(1));
+        assert_eq!(
+            Line::unfolded(b".??..??...?##.", &[1, 1, 3], 5).count(),
+            Some(16384)
+        );
+        assert_eq!(Line::new(&[b'?'; 400], &[1; 20]).count(), None);
+
+        assert_eq!(
+            Line::new(b"???.###", &[1, 1, 3]).intersect(),
+            Some(b"#.#.###".to_vec())
+        );
+        assert_eq!(
+            Line::new(b"??????????", &[8]).intersect(),
+            Some(b"??######??".to_vec())
+        );
+        assert_eq!(
+            Line::new(b"?#????", &[3]).intersect(),
+            Some(b"?##?..".to_vec())
+        );
+        assert_eq!(
+            Line::new(b"?????", &[]).intersect(),
+            Some(b".....".to_vec())
+        );
+        assert_eq!(Line::new(b"#??", &[2, 1]).intersect(), None);
+        assert_eq!(Line::new(&[b'?'; 400], &[1; 20]).forced_cells(), vec![]);
+        assert_eq!(
+            Line::new(&[b'?'; 99], &[1; 50]).intersect(),
+            Some([b"#.".repeat(49), b"#".to_vec()].concat())
+        );
+    }
+
+    #[test]
+    fn test_arrangements() {
+        let line = Line::new(b".??..??...?##.", &[1, 1, 3]);
+        let mut all = line
+            .arrangements()
+            .map(|cells| String::from_utf8(cells).expect("Should be ASCII"))
+            .collect::<Vec<_>>();
+        all.sort();
+        assert_eq!(
+            all,
+            vec![
+                ".#...#....###.",
+                ".#....#...###.",
+                "..#..#....###.",
+                "..#...#...###.",
+            ]
+        );
+        assert_eq!(
+            Line::new(b"?###????????", &[3, 2, 1])
+                .arrangements()
+                .count(),
+            10
+        );
+        assert_eq!(Line::new(b"#??", &[2, 1]).arrangements().count(), 0);
+
+        let mut rng = StdRng::seed_from_u64(2023);
+        let mut seen = HashMap::new();
+        for _ in 0..4000 {
+            let cells = line.sample(&mut rng).expect("Should have an arrangement");
+            *seen.entry(cells).or_insert(0) += 1;
+        }
+        assert_eq!(seen.len(), 4);
+        assert!(seen.values().all(|&n| (900..1100).contains(&n)));
+        assert_eq!(Line::new(b"#??", &[2, 1]).sample(&mut rng), None);
+
+        assert_eq!(line.forced_cells(), vec![(10, b'#')]);
+        assert_eq!(
+            Line::new(b"?#????", &[3]).forced_cells(),
+            vec![(2, b'#'), (4, b'.'), (5, b'.')]
+        );
+        assert_eq!(Line::new(b"#??", &[2, 1]).forced_cells(), vec![]);
+    }
+}
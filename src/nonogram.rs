@@ -0,0 +1,167 @@
+use crate::line_solver::Line;
+
+/// A picture, `true` for filled cells.
+pub type Picture = Vec<Vec<bool>>;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Solution {
+    None,
+    Unique(Picture),
+    /// Two different solutions.
+    Multiple(Picture, Picture),
+}
+
+/// The runs of filled cells of each row and column.
+pub struct Nonogram {
+    rows: Vec<Vec<usize>>,
+    columns: Vec<Vec<usize>>,
+}
+
+impl Nonogram {
+    /// Row clues, an empty line, then column clues, one line each like `3,1` (`0` if empty).
+    pub fn from(input: &str) -> Nonogram {
+        let (rows, columns) = input
+            .split_once("\n\n")
+            .expect("Should have rows and columns");
+        let clues = |section: &str| {
+            section
+                .lines()
+                .map(|line| {
+                    line.split(',')
+                        .map(|e| e.trim().parse::<usize>().expect("Should be a number"))
+                        .filter(|&e| e > 0)
+                        .collect()
+                })
+                .collect()
+        };
+
+        Nonogram {
+            rows: clues(rows),
+            columns: clues(columns),
+        }
+    }
+
+    /// Propagate each line's certain cells, then backtrack on an unknown cell.
+    pub fn solve(&self) -> Solution {
+        let grid = vec![vec![b'?'; self.columns.len()]; self.rows.len()];
+        let mut solutions = vec![];
+        self.search(grid, &mut solutions);
+
+        let mut solutions = solutions.into_iter();
+        match (solutions.next(), solutions.next()) {
+            (None, _) => Solution::None,
+            (Some(picture), None) => Solution::Unique(picture),
+            (Some(first), Some(second)) => Solution::Multiple(first, second),
+        }
+    }
+
+    /// Stops after finding two solutions.
+    fn search(&self, mut grid: Vec<Vec<u8>>, solutions: &mut Vec<Picture>) {
+        if solutions.len() >= 2 || !self.propagate(&mut grid) {
+            return;
+        }
+        let unknown = (0..grid.len())
+            .flat_map(|i| (0..grid[i].len()).map(move |j| (i, j)))
+            .find(|&(i, j)| grid[i][j] == b'?');
+        match unknown {
+            None => solutions.push(
+                grid.iter()
+                    .map(|line| line.iter().map(|&c| c == b'#').collect())
+                    .collect(),
+            ),
+            Some((i, j)) => {
+                for c in [b'#', b'.'] {
+                    let mut guess = grid.clone();
+                    guess[i][j] = c;
+                    self.search(guess, solutions);
+                }
+            }
+        }
+    }
+
+    /// Fix the cells forced by a row or column until nothing changes, `false` on a contradiction.
+    fn propagate(&self, grid: &mut [Vec<u8>]) -> bool {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, clue) in self.rows.iter().enumerate() {
+                match Line::new(&grid[i], clue).intersect() {
+                    None => return false,
+                    Some(cells) => {
+                        if cells != grid[i] {
+                            grid[i] = cells;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+            for (j, clue) in self.columns.iter().enumerate() {
+                let column: Vec<u8> = grid.iter().map(|line| line[j]).collect();
+                match Line::new(&column, clue).intersect() {
+                    None => return false,
+                    Some(cells) => {
+                        if cells != column {
+                            for (line, c) in grid.iter_mut().zip(cells) {
+                                line[j] = c;
+                            }
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Draw a picture with `#` and `.`.
+pub fn render(picture: &[Vec<bool>]) -> String {
+    picture
+        .iter()
+        .map(|line| {
+            line.iter()
+                .map(|&filled| if filled { '#' } else { '.' })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, Nonogram, Solution};
+
+    #[test]
+    fn test_solve() {
+        let nonogram = Nonogram::from(
+            "2
+1,1
+5
+1,1
+2
+
+1
+3
+1,1,1
+1,3
+2",
+        );
+        match nonogram.solve() {
+            Solution::Unique(picture) => assert_eq!(
+                render(&picture),
+                "..##.
+.#..#
+#####
+.#.#.
+..##."
+            ),
+            other => panic!("Should be unique, got {:?}", other),
+        }
+
+        let diagonals = Nonogram::from("1\n1\n\n1\n1");
+        assert!(matches!(diagonals.solve(), Solution::Multiple(_, _)));
+
+        let impossible = Nonogram::from("2\n0\n\n1\n0");
+        assert_eq!(impossible.solve(), Solution::None);
+    }
+}